@@ -2,4 +2,5 @@
 #![deny(unused_must_use)]
 #![feature(step_trait)]
 
-pub mod letter;
\ No newline at end of file
+pub mod letter;
+pub mod puzzle;
\ No newline at end of file
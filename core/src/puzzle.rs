@@ -0,0 +1,603 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::letter::{Letter, LetterSet};
+
+/// The version written by this crate. Files without a `format_version` are version 0.
+pub const FORMAT_VERSION: u32 = 1;
+
+/// How far a puzzle has been generated. Each state requires the fields of the states before it.
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum PuzzleState {
+    /// Only the quote and source.
+    Draft,
+    /// `quote_letters` and `source_letters` are filled in.
+    Lettered,
+    /// `clues` holds one answer per source letter.
+    Answered,
+    /// Every clue has text.
+    Clued,
+}
+
+/// Where a clue came from.
+#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ClueSource {
+    /// Generated with `clue_seed` and checked by asking the model to solve it with `answer_seed`.
+    Llm {
+        model: String,
+        clue_seed: i32,
+        answer_seed: i32,
+    },
+    ClueDb,
+}
+
+/// Everything needed to regenerate a puzzle exactly: which code wrote each stage, which
+/// artifacts the search read, and which seed it succeeded with. Clue provenance is kept
+/// on each [Clue].
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct Provenance {
+    /// Generator revision that wrote each stage, keyed by stage name.
+    pub revisions: BTreeMap<String, String>,
+    pub dict_hash: Option<String>,
+    pub trie_hash: Option<String>,
+    pub search_seed: Option<u64>,
+}
+
+/// How hard a puzzle's answers should be.
+#[derive(Serialize, Deserialize, Debug, Default, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum DifficultyLevel {
+    /// Common, short answers.
+    Easy,
+    #[default]
+    Medium,
+    /// Rarer, longer answers with awkward letters.
+    Hard,
+}
+
+impl Display for DifficultyLevel {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            DifficultyLevel::Easy => "easy",
+            DifficultyLevel::Medium => "medium",
+            DifficultyLevel::Hard => "hard",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for DifficultyLevel {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "easy" => Ok(DifficultyLevel::Easy),
+            "medium" => Ok(DifficultyLevel::Medium),
+            "hard" => Ok(DifficultyLevel::Hard),
+            _ => Err(format!("unknown difficulty {:?}; expected easy, medium or hard", s)),
+        }
+    }
+}
+
+/// The difficulty the answer search aimed for, and how hard the answers it found turned out.
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq)]
+pub struct Difficulty {
+    pub target: DifficultyLevel,
+    /// From 0 (easiest) to 1 (hardest), comparable across targets.
+    pub score: f64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Clue {
+    pub clue: Option<String>,
+    pub answer: String,
+    pub answer_letters: String,
+    /// For each answer letter, the position in `quote_letters` it fills.
+    pub indices: Vec<usize>,
+    pub source: Option<ClueSource>,
+    /// For phrase answers, the positions in `answer_letters` where each word after the first
+    /// starts, so players can show the spaces.
+    pub breaks: Option<Vec<usize>>,
+}
+
+impl Clue {
+    /// The [Clue::breaks] of an answer written with spaces between its words, or `None` for
+    /// a single word.
+    pub fn breaks_in(answer: &str) -> Option<Vec<usize>> {
+        let mut breaks = vec![];
+        let mut letters = 0;
+        for word in answer.split_whitespace() {
+            let count = LetterSet::from_str(word).count();
+            if letters > 0 && count > 0 {
+                breaks.push(letters);
+            }
+            letters += count;
+        }
+        Some(breaks).filter(|x| !x.is_empty())
+    }
+}
+
+/// One of several answer sets the search found, kept so an editor can compare them and swap
+/// one in before cluing.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Alternative {
+    /// In the order of `source_letters`, without clue text.
+    pub clues: Vec<Clue>,
+    /// The search's total word score; higher is better.
+    pub quality: f64,
+    pub search_seed: Option<u64>,
+    pub difficulty: Option<Difficulty>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Puzzle {
+    pub format_version: u32,
+    pub state: PuzzleState,
+    pub quote: String,
+    /// One character per cell: a letter, digit, space, hyphen or (in older puzzles) apostrophe.
+    pub quote_letters: Option<String>,
+    pub source: String,
+    pub source_letters: Option<String>,
+    /// In the order of `source_letters`.
+    pub clues: Option<Vec<Clue>>,
+    pub chat: Option<String>,
+    pub provenance: Option<Provenance>,
+    /// Set by the answer search; absent for imported and older puzzles.
+    pub difficulty: Option<Difficulty>,
+    /// Every answer set the search kept when asked for more than one, including the one in
+    /// `clues`. Ordered so each shares as few answers as possible with those before it.
+    pub alternatives: Option<Vec<Alternative>>,
+}
+
+#[derive(Debug)]
+pub enum PuzzleError {
+    Json(serde_json::Error),
+    NotAnObject,
+    UnsupportedVersion(u64),
+}
+
+impl Display for PuzzleError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PuzzleError::Json(e) => write!(f, "{}", e),
+            PuzzleError::NotAnObject => write!(f, "puzzle is not a JSON object"),
+            PuzzleError::UnsupportedVersion(v) => write!(
+                f,
+                "puzzle format version {} is newer than {}",
+                v, FORMAT_VERSION
+            ),
+        }
+    }
+}
+
+impl Error for PuzzleError {}
+
+impl From<serde_json::Error> for PuzzleError {
+    fn from(e: serde_json::Error) -> Self {
+        PuzzleError::Json(e)
+    }
+}
+
+/// A broken invariant found by [Puzzle::validate].
+#[derive(Debug, Clone, Eq, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ValidationError {
+    Version { found: u32 },
+    MissingField { field: &'static str },
+    /// A character that is not allowed in the field.
+    BadCharacter { field: &'static str, found: char },
+    ClueCount { clues: usize, source_letters: usize },
+    FirstLetter { clue: usize, expected: char, found: Option<char> },
+    IndexCount { clue: usize, indices: usize, letters: usize },
+    IndexOutOfRange { clue: usize, index: usize },
+    LetterMismatch { clue: usize, index: usize, expected: char, found: char },
+    DuplicateIndex { index: usize },
+    UnusedCell { index: usize },
+    MissingClue { clue: usize },
+    /// Breaks that are not increasing positions strictly inside the answer.
+    BadBreaks { clue: usize },
+    /// A problem with one of the puzzle's `alternatives`.
+    Alternative { alternative: usize, error: Box<ValidationError> },
+}
+
+impl Display for ValidationError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ValidationError::Version { found } => {
+                write!(f, "format version {} is not {}", found, FORMAT_VERSION)
+            }
+            ValidationError::MissingField { field } => write!(f, "missing {}", field),
+            ValidationError::BadCharacter { field, found } => {
+                write!(f, "{} contains {:?}", field, found)
+            }
+            ValidationError::ClueCount {
+                clues,
+                source_letters,
+            } => write!(f, "{} clues for {} source letters", clues, source_letters),
+            ValidationError::FirstLetter {
+                clue,
+                expected,
+                found,
+            } => write!(f, "clue {} starts with {:?}, not {:?}", clue, found, expected),
+            ValidationError::IndexCount {
+                clue,
+                indices,
+                letters,
+            } => write!(f, "clue {} has {} indices for {} letters", clue, indices, letters),
+            ValidationError::IndexOutOfRange { clue, index } => {
+                write!(f, "clue {} points past the quote at {}", clue, index)
+            }
+            ValidationError::LetterMismatch {
+                clue,
+                index,
+                expected,
+                found,
+            } => write!(
+                f,
+                "clue {} puts {:?} in cell {} holding {:?}",
+                clue, expected, index, found
+            ),
+            ValidationError::DuplicateIndex { index } => {
+                write!(f, "cell {} is used by more than one clue", index)
+            }
+            ValidationError::UnusedCell { index } => write!(f, "cell {} is not in any answer", index),
+            ValidationError::MissingClue { clue } => write!(f, "clue {} has no text", clue),
+            ValidationError::BadBreaks { clue } => write!(f, "clue {} has bad word breaks", clue),
+            ValidationError::Alternative { alternative, error } => {
+                write!(f, "alternative {}: {}", alternative, error)
+            }
+        }
+    }
+}
+
+impl Error for ValidationError {}
+
+/// Adds `state`, which version 0 left implicit in which fields were present.
+fn migrate_v0(puzzle: &mut Map<String, Value>) {
+    let has = |field: &str| puzzle.get(field).is_some_and(|x| !x.is_null());
+    let clued = puzzle
+        .get("clues")
+        .and_then(|x| x.as_array())
+        .is_some_and(|x| x.iter().all(|c| c.get("clue").is_some_and(|x| !x.is_null())));
+    let state = if has("clues") && clued {
+        PuzzleState::Clued
+    } else if has("clues") {
+        PuzzleState::Answered
+    } else if has("quote_letters") && has("source_letters") {
+        PuzzleState::Lettered
+    } else {
+        PuzzleState::Draft
+    };
+    puzzle.insert("state".to_string(), serde_json::to_value(state).unwrap());
+}
+
+/// `MIGRATIONS[v]` upgrades a version `v` puzzle to version `v + 1`.
+const MIGRATIONS: [fn(&mut Map<String, Value>); FORMAT_VERSION as usize] = [migrate_v0];
+
+impl Puzzle {
+    pub fn new(quote: String, source: String) -> Self {
+        Puzzle {
+            format_version: FORMAT_VERSION,
+            state: PuzzleState::Draft,
+            quote,
+            quote_letters: None,
+            source,
+            source_letters: None,
+            clues: None,
+            chat: None,
+            provenance: None,
+            difficulty: None,
+            alternatives: None,
+        }
+    }
+    /// Parses a puzzle of any version, migrating it to [FORMAT_VERSION].
+    pub fn from_json(input: &str) -> Result<Self, PuzzleError> {
+        let mut value: Value = serde_json::from_str(input)?;
+        let puzzle = value.as_object_mut().ok_or(PuzzleError::NotAnObject)?;
+        let version = match puzzle.get("format_version") {
+            None => 0,
+            Some(x) => x.as_u64().ok_or(PuzzleError::UnsupportedVersion(u64::MAX))?,
+        };
+        if version > FORMAT_VERSION as u64 {
+            return Err(PuzzleError::UnsupportedVersion(version));
+        }
+        for migration in &MIGRATIONS[version as usize..] {
+            migration(puzzle);
+        }
+        puzzle.insert("format_version".to_string(), FORMAT_VERSION.into());
+        Ok(serde_json::from_value(value)?)
+    }
+    pub fn provenance_mut(&mut self) -> &mut Provenance {
+        self.provenance.get_or_insert_with(Provenance::default)
+    }
+    /// Makes `alternative` the puzzle's answers, dropping any clue text.
+    pub fn use_alternative(&mut self, alternative: &Alternative) {
+        self.clues = Some(alternative.clues.clone());
+        self.difficulty = alternative.difficulty;
+        self.provenance_mut().search_seed = alternative.search_seed;
+    }
+    /// Checks that the fields required by `state` are present and consistent with each other.
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        if self.format_version != FORMAT_VERSION {
+            return Err(ValidationError::Version {
+                found: self.format_version,
+            });
+        }
+        if self.state < PuzzleState::Lettered {
+            return Ok(());
+        }
+        let quote_letters = self
+            .quote_letters
+            .as_deref()
+            .ok_or(ValidationError::MissingField {
+                field: "quote_letters",
+            })?;
+        let source_letters = self
+            .source_letters
+            .as_deref()
+            .ok_or(ValidationError::MissingField {
+                field: "source_letters",
+            })?;
+        if let Some(found) = quote_letters
+            .chars()
+            .find(|x| !(x.is_ascii_uppercase() || x.is_ascii_digit() || " -'".contains(*x)))
+        {
+            return Err(ValidationError::BadCharacter {
+                field: "quote_letters",
+                found,
+            });
+        }
+        if let Some(found) = source_letters.chars().find(|x| !x.is_ascii_uppercase()) {
+            return Err(ValidationError::BadCharacter {
+                field: "source_letters",
+                found,
+            });
+        }
+        if self.state < PuzzleState::Answered {
+            return Ok(());
+        }
+        let clues = self
+            .clues
+            .as_ref()
+            .ok_or(ValidationError::MissingField { field: "clues" })?;
+        validate_clues(quote_letters, source_letters, clues)?;
+        for (alternative, x) in self.alternatives.iter().flatten().enumerate() {
+            validate_clues(quote_letters, source_letters, &x.clues).map_err(|error| {
+                ValidationError::Alternative {
+                    alternative,
+                    error: Box::new(error),
+                }
+            })?;
+        }
+        if self.state < PuzzleState::Clued {
+            return Ok(());
+        }
+        if let Some(clue) = clues.iter().position(|x| x.clue.is_none()) {
+            return Err(ValidationError::MissingClue { clue });
+        }
+        Ok(())
+    }
+}
+
+
+/// Checks that `clues` start with the source letters and use every letter cell exactly once.
+fn validate_clues(
+    quote_letters: &str,
+    source_letters: &str,
+    clues: &[Clue],
+) -> Result<(), ValidationError> {
+    if clues.len() != source_letters.len() {
+        return Err(ValidationError::ClueCount {
+            clues: clues.len(),
+            source_letters: source_letters.len(),
+        });
+    }
+    let cells = quote_letters.as_bytes();
+    let mut used = BTreeSet::new();
+    for (i, (clue, expected)) in clues.iter().zip(source_letters.chars()).enumerate() {
+        let found = clue.answer_letters.chars().next();
+        if found != Some(expected) {
+            return Err(ValidationError::FirstLetter {
+                clue: i,
+                expected,
+                found,
+            });
+        }
+        if clue.indices.len() != clue.answer_letters.len() {
+            return Err(ValidationError::IndexCount {
+                clue: i,
+                indices: clue.indices.len(),
+                letters: clue.answer_letters.len(),
+            });
+        }
+        for (&index, expected) in clue.indices.iter().zip(clue.answer_letters.chars()) {
+            let found = *cells
+                .get(index)
+                .ok_or(ValidationError::IndexOutOfRange { clue: i, index })?
+                as char;
+            if found != expected {
+                return Err(ValidationError::LetterMismatch {
+                    clue: i,
+                    index,
+                    expected,
+                    found,
+                });
+            }
+            if !used.insert(index) {
+                return Err(ValidationError::DuplicateIndex { index });
+            }
+        }
+        if let Some(breaks) = &clue.breaks {
+            let mut previous = 0;
+            for &position in breaks {
+                if position <= previous || position >= clue.answer_letters.len() {
+                    return Err(ValidationError::BadBreaks { clue: i });
+                }
+                previous = position;
+            }
+        }
+    }
+    if let Some(index) = cells
+        .iter()
+        .enumerate()
+        .position(|(i, x)| Letter::new(*x).is_ok() && !used.contains(&i))
+    {
+        return Err(ValidationError::UnusedCell { index });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+fn example() -> Puzzle {
+    let mut puzzle = Puzzle::new("Go at it.".to_string(), "Tag".to_string());
+    puzzle.state = PuzzleState::Clued;
+    puzzle.quote_letters = Some("GO AT IT".to_string());
+    puzzle.source_letters = Some("TG".to_string());
+    let clue = |text: &str, answer: &str, indices: Vec<usize>| Clue {
+        clue: Some(text.to_string()),
+        answer: answer.to_string(),
+        answer_letters: answer.to_uppercase(),
+        indices,
+        source: None,
+        breaks: None,
+    };
+    puzzle.clues = Some(vec![
+        clue("Label", "tat", vec![4, 3, 7]),
+        clue("Departed", "goi", vec![0, 1, 6]),
+    ]);
+    puzzle
+}
+
+#[test]
+fn test_validate() {
+    let puzzle = example();
+    puzzle.validate().unwrap();
+
+    let mut swapped = example();
+    swapped.clues.as_mut().unwrap().reverse();
+    assert!(matches!(
+        swapped.validate(),
+        Err(ValidationError::FirstLetter { clue: 0, .. })
+    ));
+
+    let mut mismatch = example();
+    mismatch.clues.as_mut().unwrap()[0].indices = vec![4, 6, 7];
+    assert!(matches!(
+        mismatch.validate(),
+        Err(ValidationError::LetterMismatch { clue: 0, index: 6, .. })
+    ));
+
+    let mut unused = example();
+    unused.quote_letters = Some("GO AT ITS".to_string());
+    assert_eq!(unused.validate(), Err(ValidationError::UnusedCell { index: 8 }));
+
+    let mut unclued = example();
+    unclued.clues.as_mut().unwrap()[1].clue = None;
+    assert_eq!(unclued.validate(), Err(ValidationError::MissingClue { clue: 1 }));
+    unclued.state = PuzzleState::Answered;
+    unclued.validate().unwrap();
+
+    let mut phrase = example();
+    phrase.clues.as_mut().unwrap()[0].breaks = Some(vec![1]);
+    phrase.validate().unwrap();
+    phrase.clues.as_mut().unwrap()[0].breaks = Some(vec![3]);
+    assert_eq!(phrase.validate(), Err(ValidationError::BadBreaks { clue: 0 }));
+}
+
+#[test]
+fn test_breaks_in() {
+    assert_eq!(Clue::breaks_in("netball"), None);
+    assert_eq!(Clue::breaks_in("ice cream"), Some(vec![3]));
+    assert_eq!(Clue::breaks_in("take a  break"), Some(vec![4, 5]));
+    assert_eq!(Clue::breaks_in("rock 'n' roll"), Some(vec![4, 5]));
+}
+
+#[test]
+fn test_migrate_v0() {
+    let v0 = r#"{
+        "quote": "Go at it.",
+        "quote_letters": "GO AT IT",
+        "source": "Tag",
+        "source_letters": "TG",
+        "clues": null,
+        "chat": null
+    }"#;
+    let puzzle = Puzzle::from_json(v0).unwrap();
+    assert_eq!(puzzle.format_version, FORMAT_VERSION);
+    assert_eq!(puzzle.state, PuzzleState::Lettered);
+    puzzle.validate().unwrap();
+
+    let json = serde_json::to_string(&example()).unwrap();
+    let round_trip = Puzzle::from_json(&json).unwrap();
+    assert_eq!(round_trip.state, PuzzleState::Clued);
+    round_trip.validate().unwrap();
+
+    assert!(matches!(
+        Puzzle::from_json(r#"{"format_version": 99}"#),
+        Err(PuzzleError::UnsupportedVersion(99))
+    ));
+}
+
+#[test]
+fn test_clue_source_json() {
+    let source = ClueSource::Llm {
+        model: "phi4".to_string(),
+        clue_seed: 123455454,
+        answer_seed: 123665,
+    };
+    let json = serde_json::to_string(&source).unwrap();
+    assert_eq!(
+        json,
+        r#"{"kind":"llm","model":"phi4","clue_seed":123455454,"answer_seed":123665}"#
+    );
+    assert_eq!(serde_json::from_str::<ClueSource>(&json).unwrap(), source);
+    assert_eq!(serde_json::to_string(&ClueSource::ClueDb).unwrap(), r#"{"kind":"clue_db"}"#);
+}
+
+#[test]
+fn test_difficulty_json() {
+    let difficulty = Difficulty {
+        target: DifficultyLevel::Hard,
+        score: 0.5,
+    };
+    let json = serde_json::to_string(&difficulty).unwrap();
+    assert_eq!(json, r#"{"target":"hard","score":0.5}"#);
+    assert_eq!(serde_json::from_str::<Difficulty>(&json).unwrap(), difficulty);
+    for level in [DifficultyLevel::Easy, DifficultyLevel::Medium, DifficultyLevel::Hard] {
+        assert_eq!(level.to_string().parse::<DifficultyLevel>(), Ok(level));
+    }
+    assert!("brutal".parse::<DifficultyLevel>().is_err());
+}
+
+#[test]
+fn test_alternatives() {
+    let mut puzzle = example();
+    puzzle.state = PuzzleState::Answered;
+    let mut clues = puzzle.clues.clone().unwrap();
+    clues[0].indices = vec![7, 3, 4];
+    let alternative = Alternative {
+        clues,
+        quality: 1.5,
+        search_seed: Some(7),
+        difficulty: None,
+    };
+    puzzle.alternatives = Some(vec![alternative.clone()]);
+    puzzle.validate().unwrap();
+    puzzle.use_alternative(&alternative);
+    assert_eq!(puzzle.clues.as_ref().unwrap()[0].indices, vec![7, 3, 4]);
+    assert_eq!(puzzle.provenance.as_ref().unwrap().search_seed, Some(7));
+
+    let mut broken = alternative;
+    broken.clues[0].indices = vec![4, 6, 7];
+    puzzle.alternatives = Some(vec![broken]);
+    assert!(matches!(
+        puzzle.validate(),
+        Err(ValidationError::Alternative { alternative: 0, .. })
+    ));
+}
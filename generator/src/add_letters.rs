@@ -1,5 +1,6 @@
 use std::io;
-use crate::puzzle::Puzzle;
+use crate::pipeline::Stage;
+use crate::puzzle::{Puzzle, PuzzleFile};
 use crate::string::{GraphemeString, LetterString};
 use std::fmt::Write;
 // pub fn segment(s: &str) -> Vec<EitherOrBoth<Letter, String>> {
@@ -27,7 +28,7 @@ use std::fmt::Write;
 //     result
 // }
 
-fn quote_to_cells(input: &str) -> String {
+pub fn quote_to_cells(input: &str) -> String {
     let mut cells = String::new();
     for grapheme in GraphemeString::from_str(&input).graphemes() {
         if grapheme.letters().is_empty() {
@@ -61,14 +62,14 @@ fn quote_to_cells(input: &str) -> String {
 
 
 pub async fn add_letters(pindex: usize) -> io::Result<()> {
-    let mut puzzle = Puzzle::read(pindex, "stage0.json").await?;
+    let mut puzzle = Puzzle::read(pindex, Stage::Quote).await?;
     puzzle
         .quote_letters
         .get_or_insert_with(|| quote_to_cells(&puzzle.quote));
     puzzle.source_letters.get_or_insert_with(|| {
         LetterString::from_str(&puzzle.source).iter().map(|x| x.to_char()).collect()
     });
-    puzzle.write(pindex, "stage1.json").await?;
+    puzzle.write(pindex, Stage::Letters).await?;
     Ok(())
 }
 
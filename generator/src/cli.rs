@@ -0,0 +1,539 @@
+use std::ops::RangeInclusive;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::Duration;
+
+use anyhow::anyhow;
+use clap::{Args, Parser, Subcommand, ValueEnum};
+
+use crate::export::ExportFormat;
+use crate::pipeline::Stage;
+use crate::puzzle::DifficultyLevel;
+use crate::query::Pattern;
+use crate::search::{
+    AnswerConstraints, LengthConstraints, PinnedAnswer, SearchOptions, SolverMode,
+    DEFAULT_EXACT_BUDGET,
+};
+use crate::quote::QuoteFilter;
+use crate::read_path_to_string;
+
+#[derive(Parser, Debug)]
+#[command(about = "Builds the acrostic dictionary and generates puzzles")]
+pub struct Cli {
+    /// Root directory containing `build/`, `submodules/` and `player/` (defaults to the repository root).
+    #[arg(long, global = true)]
+    pub data_dir: Option<PathBuf>,
+
+    /// Directory containing one subdirectory per puzzle (defaults to `<data-dir>/puzzles`).
+    #[arg(long, global = true)]
+    pub puzzles_dir: Option<PathBuf>,
+
+    /// TOML file choosing the dictionary words the tries are built from and searched (see
+    /// `dict_manifest.rs`); the default keeps the 15,000 most frequent words of six letters
+    /// or more.
+    #[arg(long, global = true)]
+    pub dict_manifest: Option<PathBuf>,
+
+    #[command(subcommand)]
+    pub command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Build a shared artifact under `build/`.
+    Global {
+        #[arg(value_enum)]
+        target: GlobalTarget,
+    },
+    /// Advance a set of puzzles through the generation stages.
+    Puzzle {
+        #[command(subcommand)]
+        stage: PuzzleCommand,
+    },
+    /// Generate a themed pack of puzzles and write its manifest to `packs/`.
+    Pack(PackArgs),
+    /// Check every stage file for problems, printing one JSON object per problem.
+    Validate(ValidateArgs),
+    /// Convert clued puzzles for other solving apps, writing to `build/export/`.
+    Export(ExportArgs),
+    /// Draw printable clued puzzles and answer keys, writing to `build/render/`.
+    Render(RenderArgs),
+    /// Bring a hand-made acrostic in as a puzzle's answers stage, ready for cluing.
+    Import(ImportArgs),
+    /// List a puzzle's alternative answer sets, or make one of them its answers.
+    Pick(PickArgs),
+    /// Look up dictionary words by their letters, for fixing up answer sets by hand.
+    Query(QueryArgs),
+    /// Swap answers in a puzzle's answers stage interactively, refitting the others around
+    /// each change.
+    Edit(EditArgs),
+}
+
+#[derive(ValueEnum, Copy, Clone, Debug)]
+pub enum GlobalTarget {
+    /// Parse Quotes-500K into `build/quotes.json`.
+    Quotes,
+    /// Build `build/dict.dat` from the Wikipedia word frequency list, with phrases from
+    /// `build/turtle.dat`.
+    Dict,
+    /// Build the unary and binary tries for `--dict-manifest` under `build/tries/`.
+    Trie,
+    /// Assemble the static site under `build/site`.
+    Site,
+    /// Parse the dbnary turtle files into `build/turtle.dat`.
+    Turtle,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum PuzzleCommand {
+    /// Select a quote: writes `stage0.json`.
+    Quote(QuoteArgs),
+    /// Split the quote and source into letters: `stage0.json` to `stage1.json`.
+    Letters(StageArgs),
+    /// Search for answer words: `stage1.json` to `stage2.json`.
+    Answers(AnswersArgs),
+    /// Write clues with the LLM: `stage2.json` to `stage3.json`.
+    Chat(StageArgs),
+    /// Run every stage in order, skipping stages whose output is newer than their input.
+    All(PipelineArgs),
+}
+
+impl PuzzleCommand {
+    /// The stage and arguments of a command that runs exactly one stage.
+    pub fn single_stage(&self) -> Option<(Stage, &StageArgs)> {
+        match self {
+            PuzzleCommand::Quote(args) => Some((Stage::Quote, &args.stage)),
+            PuzzleCommand::Letters(args) => Some((Stage::Letters, args)),
+            PuzzleCommand::Answers(args) => Some((Stage::Answers, &args.stage)),
+            PuzzleCommand::Chat(args) => Some((Stage::Chat, args)),
+            PuzzleCommand::All(_) => None,
+        }
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct StageArgs {
+    /// Puzzle indices, either single (`12`) or inclusive ranges (`10-20`).
+    #[arg(required = true)]
+    pub puzzles: Vec<PuzzleRange>,
+
+    /// Number of puzzles processed at once.
+    #[arg(long, default_value_t = 1)]
+    pub concurrency: usize,
+}
+
+#[derive(Args, Debug)]
+pub struct AnswersArgs {
+    #[command(flatten)]
+    pub stage: StageArgs,
+
+    #[command(flatten)]
+    pub search: SearchArgs,
+}
+
+#[derive(Args, Debug)]
+pub struct SearchArgs {
+    /// How hard the answer words should be.
+    #[arg(long, default_value_t)]
+    pub difficulty: DifficultyLevel,
+
+    /// Shortest allowed answer, in letters.
+    #[arg(long, default_value_t = 2)]
+    pub min_answer_len: usize,
+
+    /// Longest allowed answer, in letters.
+    #[arg(long)]
+    pub max_answer_len: Option<usize>,
+
+    /// Reject puzzles whose mean answer length (quote letters per source letter) is more than
+    /// one letter from this.
+    #[arg(long)]
+    pub target_mean_len: Option<f64>,
+
+    /// Answers longer than this count as long for `--max-long-answers`.
+    #[arg(long, default_value_t = 10)]
+    pub long_answer_len: usize,
+
+    /// Most long answers allowed in one puzzle.
+    #[arg(long)]
+    pub max_long_answers: Option<usize>,
+
+    #[arg(long, value_enum, default_value_t)]
+    pub solver: SolverMode,
+
+    /// Nodes the exact solver may visit before giving up.
+    #[arg(long, default_value_t = DEFAULT_EXACT_BUDGET)]
+    pub exact_budget: usize,
+
+    /// Fix the answer at a source position (from 0), as `POSITION=WORD`; repeatable, and
+    /// added to the puzzle's `answers.toml`.
+    #[arg(long = "pin")]
+    pub pinned: Vec<PinnedAnswer>,
+
+    /// Never use this word as an answer; repeatable.
+    #[arg(long = "exclude")]
+    pub excluded: Vec<String>,
+
+    /// Give up on a puzzle's answer search after this many seconds.
+    #[arg(long)]
+    pub time_limit: Option<f64>,
+
+    /// Seconds between progress reports while searching; 0 turns them off.
+    #[arg(long, default_value_t = 10.0)]
+    pub progress_interval: f64,
+
+    /// Keep up to this many answer sets, sharing as few answers as possible, for `pick`.
+    #[arg(long, default_value_t = 1)]
+    pub alternatives: usize,
+}
+
+impl SearchArgs {
+    pub fn options(&self) -> SearchOptions {
+        SearchOptions {
+            difficulty: self.difficulty,
+            lengths: LengthConstraints {
+                min_len: self.min_answer_len,
+                max_len: self.max_answer_len.unwrap_or(usize::MAX),
+                target_mean: self.target_mean_len,
+                long_len: self.long_answer_len,
+                max_long: self.max_long_answers.unwrap_or(usize::MAX),
+            },
+            solver: self.solver,
+            exact_budget: self.exact_budget,
+            answers: AnswerConstraints {
+                pin: self.pinned.clone(),
+                exclude: self.excluded.clone(),
+            },
+            time_limit: self.time_limit.map(Duration::from_secs_f64),
+            progress_interval: (self.progress_interval > 0.0)
+                .then(|| Duration::from_secs_f64(self.progress_interval)),
+            alternatives: self.alternatives,
+        }
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct PipelineArgs {
+    /// Puzzle indices, either single (`12`) or inclusive ranges (`10-20`).
+    #[arg(required = true)]
+    pub puzzles: Vec<PuzzleRange>,
+
+    #[command(flatten)]
+    pub concurrency: ConcurrencyArgs,
+
+    /// Last stage to run.
+    #[arg(long, value_enum, default_value_t = Stage::Chat)]
+    pub until: Stage,
+
+    #[command(flatten)]
+    pub search: SearchArgs,
+
+    #[command(flatten)]
+    pub quote_filter: QuoteFilterArgs,
+}
+
+#[derive(Args, Debug)]
+pub struct ConcurrencyArgs {
+    /// Number of CPU-bound stages (quote, letters, answers) running at once.
+    #[arg(long, default_value_t = 1)]
+    pub cpu_concurrency: usize,
+
+    /// Number of LLM-bound stages (chat) running at once.
+    #[arg(long, default_value_t = 1)]
+    pub llm_concurrency: usize,
+}
+
+#[derive(Args, Debug)]
+pub struct PackArgs {
+    /// Topic from the quotes' topic column, e.g. `love` or `science`.
+    pub topic: String,
+
+    /// Number of puzzles in the pack.
+    #[arg(long, default_value_t = 7)]
+    pub count: usize,
+
+    /// File name of the manifest under `packs/` (defaults to the topic).
+    #[arg(long)]
+    pub name: Option<String>,
+
+    /// Heading shown on the index page (defaults to the capitalized topic).
+    #[arg(long)]
+    pub title: Option<String>,
+
+    #[arg(long, default_value = "")]
+    pub description: String,
+
+    #[command(flatten)]
+    pub search: SearchArgs,
+
+    #[command(flatten)]
+    pub concurrency: ConcurrencyArgs,
+
+    #[command(flatten)]
+    pub quote_filter: QuoteFilterArgs,
+}
+
+#[derive(Args, Debug)]
+pub struct ValidateArgs {
+    /// Puzzle indices, either single (`12`) or inclusive ranges (`10-20`); all puzzles if omitted.
+    pub puzzles: Vec<PuzzleRange>,
+
+    /// Also load the `--dict-manifest` trie table, which checks the hash of every trie in it.
+    #[arg(long)]
+    pub tries: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct ExportArgs {
+    #[arg(long, value_enum)]
+    pub format: ExportFormat,
+
+    /// Puzzle indices, either single (`12`) or inclusive ranges (`10-20`).
+    #[arg(required = true)]
+    pub puzzles: Vec<PuzzleRange>,
+}
+
+#[derive(Args, Debug)]
+pub struct QuoteArgs {
+    #[command(flatten)]
+    pub stage: StageArgs,
+
+    #[command(flatten)]
+    pub quote_filter: QuoteFilterArgs,
+}
+
+/// Quote selection criteria. Flags override the file, which overrides the defaults.
+#[derive(Args, Debug, Default)]
+pub struct QuoteFilterArgs {
+    /// TOML file containing a quote filter.
+    #[arg(long)]
+    pub quote_filter: Option<PathBuf>,
+
+    #[arg(long)]
+    pub min_quote_letters: Option<usize>,
+
+    #[arg(long)]
+    pub max_quote_letters: Option<usize>,
+
+    #[arg(long)]
+    pub min_source_letters: Option<usize>,
+
+    #[arg(long)]
+    pub max_source_letters: Option<usize>,
+
+    /// Only accept quotes with this topic (repeatable).
+    #[arg(long = "topic")]
+    pub topics: Vec<String>,
+
+    /// Never accept quotes by this author (repeatable).
+    #[arg(long = "ban-author")]
+    pub banned_authors: Vec<String>,
+
+    /// Accept quotes whose letters cannot cover the source.
+    #[arg(long)]
+    pub skip_letter_check: bool,
+}
+
+impl QuoteFilterArgs {
+    pub async fn load(&self) -> anyhow::Result<QuoteFilter> {
+        let mut filter = match &self.quote_filter {
+            Some(path) => QuoteFilter::from_toml(&read_path_to_string(path).await?)?,
+            None => QuoteFilter::default(),
+        };
+        if let Some(x) = self.min_quote_letters {
+            filter.min_quote_letters = x;
+        }
+        if let Some(x) = self.max_quote_letters {
+            filter.max_quote_letters = x;
+        }
+        if let Some(x) = self.min_source_letters {
+            filter.min_source_letters = x;
+        }
+        if let Some(x) = self.max_source_letters {
+            filter.max_source_letters = x;
+        }
+        if !self.topics.is_empty() {
+            filter.topics = self.topics.clone();
+        }
+        filter.banned_authors.extend(self.banned_authors.iter().cloned());
+        if self.skip_letter_check {
+            filter.check_letters = false;
+        }
+        Ok(filter)
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PuzzleRange(RangeInclusive<usize>);
+
+impl PuzzleRange {
+    pub fn iter(&self) -> RangeInclusive<usize> {
+        self.0.clone()
+    }
+}
+
+impl FromStr for PuzzleRange {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parse = |x: &str| {
+            x.trim()
+                .parse::<usize>()
+                .map_err(|e| anyhow!("invalid puzzle index {:?}: {}", x, e))
+        };
+        let (start, end) = match s.split_once("-") {
+            Some((start, end)) => (parse(start)?, parse(end)?),
+            None => {
+                let index = parse(s)?;
+                (index, index)
+            }
+        };
+        if start > end {
+            return Err(anyhow!("empty puzzle range {:?}", s));
+        }
+        Ok(PuzzleRange(start..=end))
+    }
+}
+
+fn puzzle_indices(ranges: &[PuzzleRange]) -> Vec<usize> {
+    let mut puzzles: Vec<usize> = ranges.iter().flat_map(|x| x.iter()).collect();
+    puzzles.sort();
+    puzzles.dedup();
+    puzzles
+}
+
+impl StageArgs {
+    pub fn puzzle_indices(&self) -> Vec<usize> {
+        puzzle_indices(&self.puzzles)
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct RenderArgs {
+    /// Also write a PDF of the puzzle pages followed by the answer key pages.
+    #[arg(long)]
+    pub pdf: bool,
+
+    /// Puzzle indices, either single (`12`) or inclusive ranges (`10-20`).
+    #[arg(required = true)]
+    pub puzzles: Vec<PuzzleRange>,
+}
+
+#[derive(Args, Debug)]
+pub struct ImportArgs {
+    /// An `.ipuz` acrostic, or a text file with the quote, the source and one answer per line.
+    pub file: PathBuf,
+
+    /// Index of the puzzle to create.
+    #[arg(long)]
+    pub puzzle: usize,
+
+    /// Replace the puzzle's existing stage files.
+    #[arg(long)]
+    pub force: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct PickArgs {
+    /// Index of the puzzle whose answers stage to change.
+    pub puzzle: usize,
+
+    /// Use the alternative at this position in the list.
+    #[arg(long, conflicts_with = "best")]
+    pub index: Option<usize>,
+
+    /// Use the alternative with the highest search score.
+    #[arg(long)]
+    pub best: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct EditArgs {
+    /// Index of the puzzle whose answers stage to edit.
+    pub puzzle: usize,
+
+    /// The limits answers must keep to; these should match the puzzle's answer search.
+    #[command(flatten)]
+    pub search: SearchArgs,
+}
+
+#[derive(Args, Debug)]
+pub struct QueryArgs {
+    #[command(subcommand)]
+    pub query: QueryCommand,
+
+    /// Print at most this many words, most frequent first.
+    #[arg(long, global = true, default_value_t = 50)]
+    pub limit: usize,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum QueryCommand {
+    /// Words using exactly the given letters.
+    Anagram { letters: String },
+    /// Words using only the given letters, or those a puzzle's other answers leave free.
+    Within {
+        #[arg(required_unless_present = "puzzle")]
+        letters: Option<String>,
+
+        /// Use the quote letters of this puzzle's answers stage not taken by other answers.
+        #[arg(long, requires = "answer", conflicts_with = "letters")]
+        puzzle: Option<usize>,
+
+        /// With `--puzzle`, the answer to replace; words must start with its source letter.
+        #[arg(long, requires = "puzzle")]
+        answer: Option<usize>,
+
+        /// Only words starting with this letter.
+        #[arg(long)]
+        first: Option<char>,
+
+        /// Only words with at least this many letters.
+        #[arg(long)]
+        min_len: Option<usize>,
+
+        /// Only words with at most this many letters.
+        #[arg(long)]
+        max_len: Option<usize>,
+    },
+    /// Words matching a crossword-style pattern such as `?A??E`.
+    Pattern {
+        pattern: Pattern,
+
+        /// Only words using these letters.
+        #[arg(long)]
+        within: Option<String>,
+    },
+}
+
+impl PipelineArgs {
+    pub fn puzzle_indices(&self) -> Vec<usize> {
+        puzzle_indices(&self.puzzles)
+    }
+}
+
+impl ExportArgs {
+    pub fn puzzle_indices(&self) -> Vec<usize> {
+        puzzle_indices(&self.puzzles)
+    }
+}
+
+impl RenderArgs {
+    pub fn puzzle_indices(&self) -> Vec<usize> {
+        puzzle_indices(&self.puzzles)
+    }
+}
+
+impl ValidateArgs {
+    pub fn puzzle_indices(&self) -> Vec<usize> {
+        puzzle_indices(&self.puzzles)
+    }
+}
+
+#[test]
+fn test_puzzle_range() {
+    assert_eq!("12".parse::<PuzzleRange>().unwrap(), PuzzleRange(12..=12));
+    assert_eq!("3-7".parse::<PuzzleRange>().unwrap(), PuzzleRange(3..=7));
+    assert!("7-3".parse::<PuzzleRange>().is_err());
+    assert!("x".parse::<PuzzleRange>().is_err());
+    assert!("1-".parse::<PuzzleRange>().is_err());
+}
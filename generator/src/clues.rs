@@ -17,13 +17,14 @@ use std::{fs, future, mem};
 use tokio::{io, spawn};
 // use crate::gpt::cache_client::CacheClient;
 use crate::llm::chat_client::{BaseClient, ChatClient};
-use crate::llm::new_client;
-use crate::llm::rpcs::{AnswerRequest, ClueRequest};
+use crate::llm::rpcs::{AnswerRequest, ClueRequest, DEFAULT_SEED};
+use crate::llm::{new_client, MODEL_NAME};
 // use crate::gpt::types::{ChatMessage, ChatRequest, ChatRequestBody, ChatRole, Endpoint, FinishReason, Model};
 use crate::ontology::{Ontology, ONTOLOGY};
 use crate::PACKAGE_PATH;
 
-use crate::puzzle::Puzzle;
+use crate::pipeline::Stage;
+use crate::puzzle::{ClueSource, Puzzle, PuzzleFile};
 use crate::string::LetterString;
 use crate::subseq::longest_subsequence;
 use crate::util::interrupt::{channel, CleanupSender};
@@ -31,28 +32,22 @@ use crate::util::lazy_async::CloneError;
 
 static MODEL: &str = "llama3.2:3b";
 // static MODEL: &str = "llama3.3:70b";
-pub struct ClueClient {
-    client: Arc<dyn ChatClient>,
+/// Judges clue text against an answer without talking to the LLM.
+pub struct ClueScorer {
     ontology: Arc<Ontology>,
     lemma: Arc<Lemma>,
-    clue_db: Arc<ClueDb>,
 }
 
-impl ClueClient {
-    pub async fn new(cleanup: CleanupSender) -> anyhow::Result<Self> {
-        let client = new_client(cleanup).await?;
-        Ok(ClueClient {
-            client,
+impl ClueScorer {
+    pub async fn new() -> anyhow::Result<Self> {
+        Ok(ClueScorer {
             ontology: ONTOLOGY.get().await.clone_error_static()?.clone(),
             lemma: LEMMA.get().await.clone_error_static()?.clone(),
-            clue_db: CLUE_DB.get().await.clone_error_static()?.clone(),
         })
     }
-    pub fn score(&self, word: &str, clue: &str) -> Option<NotNan<f64>> {
-        let word_letters = LetterString::from_str(word);
+    /// A form of `word` that appears in `clue` and so gives it away.
+    pub fn conflict(&self, word: &str, clue: &str) -> Option<String> {
         let clue_letters = LetterString::from_str(clue);
-        let mut is_banned = false;
-
         for banned in self
             .lemma
             .alternates(word)
@@ -66,26 +61,51 @@ impl ClueClient {
                     .windows(banned_letters.len())
                     .any(|x| x == &*banned_letters)
                 {
-                    // eprintln!("clue {:?} contains {:?} which is banned for {:?}", clue, banned, word);
-                    is_banned = true;
+                    return Some(banned.to_string());
                 }
             }
         }
-        if is_banned {
-            None
-        } else {
-            Some(-(NotNan::new(longest_subsequence(&word_letters, &clue_letters) as f64).unwrap()))
+        None
+    }
+    /// Higher is better; `None` if the clue gives the answer away.
+    pub fn score(&self, word: &str, clue: &str) -> Option<NotNan<f64>> {
+        if self.conflict(word, clue).is_some() {
+            return None;
         }
+        let word_letters = LetterString::from_str(word);
+        let clue_letters = LetterString::from_str(clue);
+        Some(-(NotNan::new(longest_subsequence(&word_letters, &clue_letters) as f64).unwrap()))
+    }
+}
+
+pub struct ClueClient {
+    client: Arc<dyn ChatClient>,
+    scorer: ClueScorer,
+    clue_db: Arc<ClueDb>,
+}
+
+impl ClueClient {
+    pub async fn new(cleanup: CleanupSender) -> anyhow::Result<Self> {
+        let client = new_client(cleanup).await?;
+        Ok(ClueClient {
+            client,
+            scorer: ClueScorer::new().await?,
+            clue_db: CLUE_DB.get().await.clone_error_static()?.clone(),
+        })
+    }
+    pub fn score(&self, word: &str, clue: &str) -> Option<NotNan<f64>> {
+        self.scorer.score(word, clue)
     }
-    pub async fn create_clue(&self, answer: &str) -> anyhow::Result<Option<String>> {
+    pub async fn create_clue(&self, answer: &str) -> anyhow::Result<Option<(String, ClueSource)>> {
         println!("creating clue for `{}`", answer);
         for seed in 0..10 {
+            let clue_seed = 123455454 + seed;
             let mut clues = ClueRequest {
                 answer: answer.to_string(),
                 clue_count: 10,
             }
             .build()?
-            .seed(123455454 + seed)
+            .seed(clue_seed)
             .send(&*self.client)
             .await?
             .clues;
@@ -121,32 +141,40 @@ impl ClueClient {
                     )
                 }) {
                     println!("       Done! `{}` <= `{}`", answer, clue);
-                    return Ok(Some(clue));
+                    let source = ClueSource::Llm {
+                        model: MODEL_NAME.to_string(),
+                        clue_seed,
+                        answer_seed: DEFAULT_SEED,
+                    };
+                    return Ok(Some((clue, source)));
                 }
             }
         }
         if let Some(entry) = self.clue_db.lookup(&LetterString::from_str(answer)).first() {
             println!("       Used backup database for {}: {}", answer, entry.clue);
-            return Ok(Some(entry.clue.clone()));
+            return Ok(Some((entry.clue.clone(), ClueSource::ClueDb)));
         }
         println!("       Failed to generate clue for {}", answer);
         Ok(None)
     }
 }
 
-fn contains_subsequence<T: Eq>(haystack: &[T], needle: &[T]) -> bool {
+pub fn contains_subsequence<T: Eq>(haystack: &[T], needle: &[T]) -> bool {
     haystack.windows(needle.len()).any(|x| x == needle)
 }
 
 pub async fn add_chat(pindex: usize, client: &ClueClient) -> anyhow::Result<()> {
-    let mut puzzle = Puzzle::read(pindex, "stage2.json").await?;
+    let mut puzzle = Puzzle::read(pindex, Stage::Answers).await?;
     let clues = puzzle
         .clues
         .as_mut()
         .unwrap()
         .iter_mut()
         .map(|clue| async move {
-            clue.clue = client.create_clue(&clue.answer).await?;
+            if let Some((text, source)) = client.create_clue(&clue.answer).await? {
+                clue.clue = Some(text);
+                clue.source = Some(source);
+            }
             anyhow::Result::<_>::Ok(())
         });
     for clue in clues {
@@ -160,7 +188,7 @@ pub async fn add_chat(pindex: usize, client: &ClueClient) -> anyhow::Result<()>
         .iter()
         .all(|x| x.clue.is_some())
     {
-        puzzle.write(pindex, "stage3.json").await?;
+        puzzle.write(pindex, Stage::Chat).await?;
         Ok(())
     } else {
         Err(anyhow!("failed to generate clues"))
@@ -0,0 +1,331 @@
+//! Explaining why the answer search failed on a quote, so an editor can fix the quote rather
+//! than discard it. The report is printed and written to `diagnosis.json` beside the puzzle's
+//! stage files.
+
+use std::fmt::{self, Display, Formatter};
+use std::io;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use acrostic_core::letter::{Letter, LetterMap, LetterSet};
+use serde::Serialize;
+
+use crate::dict::FlatWord;
+use crate::exact::ExactOutcome;
+use crate::search::{Deadline, Search};
+use crate::trie_table::FLAT_TRIE_TABLE;
+use crate::util::lazy_async::CloneError;
+use crate::{write_path, PUZZLES_PATH};
+
+/// Letters left over by at least this fraction of failed attempts are reported.
+const LEFTOVER_RATE: f64 = 0.5;
+
+/// Source letters with fewer candidate answers than this, per answer they start, are reported.
+const SPARSE_CANDIDATES: usize = 20;
+
+/// Quote letters more than this many times as common as in the dictionary are reported.
+const IMBALANCE_RATIO: f64 = 2.5;
+
+/// A letter that failed attempts kept leaving unused.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Leftover {
+    pub letter: Letter,
+    /// The fraction of failed attempts that ended with this letter unused.
+    pub rate: f64,
+}
+
+/// A source letter with few words to choose from.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SparseSource {
+    pub letter: Letter,
+    /// Answers that must start with the letter.
+    pub answers: usize,
+    /// Allowed words starting with the letter that fit in the quote.
+    pub candidates: usize,
+}
+
+/// A letter the quote has far more of than answer words usually use.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Imbalance {
+    pub letter: Letter,
+    pub count: usize,
+    /// The count a quote of this length would have at dictionary frequencies.
+    pub expected: f64,
+}
+
+/// Whether the quote becomes solvable without one of its clauses.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Trim {
+    pub clause: String,
+    /// `None` when the exact solver ran out of budget or time before deciding.
+    pub solvable: Option<bool>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Diagnosis {
+    pub failed_attempts: usize,
+    pub leftovers: Vec<Leftover>,
+    pub sparse_sources: Vec<SparseSource>,
+    pub imbalances: Vec<Imbalance>,
+    /// Empty when the quote has a single clause.
+    pub trims: Vec<Trim>,
+}
+
+impl Diagnosis {
+    pub fn path(pindex: usize) -> PathBuf {
+        PUZZLES_PATH
+            .join(format!("{}", pindex))
+            .join("diagnosis.json")
+    }
+    pub async fn write(&self, pindex: usize) -> anyhow::Result<()> {
+        write_path(
+            &Self::path(pindex),
+            serde_json::to_string_pretty(self)?.as_bytes(),
+        )
+        .await?;
+        Ok(())
+    }
+    /// Deletes a stale report once the puzzle has been solved.
+    pub async fn remove(pindex: usize) -> io::Result<()> {
+        match tokio::fs::remove_file(Self::path(pindex)).await {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl Display for Diagnosis {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        writeln!(f, "failed attempts: {}", self.failed_attempts)?;
+        if !self.leftovers.is_empty() {
+            let leftovers: Vec<String> = self
+                .leftovers
+                .iter()
+                .map(|x| format!("{} ({:.0}%)", x.letter, 100.0 * x.rate))
+                .collect();
+            writeln!(f, "often left over: {}", leftovers.join(", "))?;
+        }
+        if !self.sparse_sources.is_empty() {
+            let sparse: Vec<String> = self
+                .sparse_sources
+                .iter()
+                .map(|x| {
+                    format!(
+                        "{} ({} words for {} answers)",
+                        x.letter, x.candidates, x.answers
+                    )
+                })
+                .collect();
+            writeln!(f, "few candidate answers: {}", sparse.join(", "))?;
+        }
+        if !self.imbalances.is_empty() {
+            let imbalances: Vec<String> = self
+                .imbalances
+                .iter()
+                .map(|x| format!("{} ({}, expected {:.1})", x.letter, x.count, x.expected))
+                .collect();
+            writeln!(f, "more than usual: {}", imbalances.join(", "))?;
+        }
+        for trim in &self.trims {
+            match trim.solvable {
+                Some(true) => writeln!(f, "dropping {:?} leaves a solvable quote", trim.clause)?,
+                Some(false) => {}
+                None => writeln!(
+                    f,
+                    "dropping {:?} might help; the check gave up",
+                    trim.clause
+                )?,
+            }
+        }
+        if !self.trims.is_empty() && self.trims.iter().all(|x| x.solvable == Some(false)) {
+            writeln!(f, "dropping any one clause still leaves it unsolvable")?;
+        }
+        Ok(())
+    }
+}
+
+/// The letters left unused by at least [LEFTOVER_RATE] of `failed` attempts, most often first.
+pub fn leftovers(failed: usize, counts: &LetterMap<usize>) -> Vec<Leftover> {
+    if failed == 0 {
+        return vec![];
+    }
+    let mut leftovers: Vec<Leftover> = counts
+        .iter()
+        .map(|(letter, count)| Leftover {
+            letter,
+            rate: *count as f64 / failed as f64,
+        })
+        .filter(|x| x.rate >= LEFTOVER_RATE)
+        .collect();
+    leftovers.sort_by(|a, b| b.rate.total_cmp(&a.rate));
+    leftovers
+}
+
+/// How often each letter appears across `words`, as a fraction of all their letters.
+pub fn letter_shares<'a>(words: impl IntoIterator<Item = &'a FlatWord>) -> LetterMap<f64> {
+    let mut totals = LetterMap::<usize>::new();
+    for word in words {
+        for letter in word.letter_vec.iter() {
+            totals[*letter] += 1;
+        }
+    }
+    let sum = totals.iter().map(|(_, x)| *x).sum::<usize>().max(1);
+    totals.map(|x| x as f64 / sum as f64)
+}
+
+/// Letters appearing at least twice in `quote` and more than [IMBALANCE_RATIO] times as often
+/// as `shares` predicts.
+pub fn imbalances(quote: LetterSet, shares: &LetterMap<f64>) -> Vec<Imbalance> {
+    quote
+        .iter()
+        .map(|(letter, count)| Imbalance {
+            letter,
+            count,
+            expected: shares[letter] * quote.count() as f64,
+        })
+        .filter(|x| x.count >= 2 && x.count as f64 > IMBALANCE_RATIO * x.expected)
+        .collect()
+}
+
+/// Splits a quote at punctuation that usually ends a clause.
+pub fn clauses(quote: &str) -> Vec<String> {
+    quote
+        .split(|c: char| ",;:.!?()\u{2013}\u{2014}".contains(c))
+        .map(|x| x.trim())
+        .filter(|x| x.chars().any(|c| c.is_alphabetic()))
+        .map(|x| x.to_string())
+        .collect()
+}
+
+/// Checks what kept `search` from solving the puzzle whose quote text is `quote`, spending up
+/// to `budget` exact-solver nodes on each clause it tries dropping. The clause checks give up
+/// once `deadline` passes.
+pub fn diagnose(
+    search: &Search,
+    quote: &str,
+    shares: &LetterMap<f64>,
+    budget: usize,
+    deadline: Deadline,
+) -> Diagnosis {
+    let (failed_attempts, leftover_counts) = search.leftovers();
+    let mut answers = LetterMap::<usize>::new();
+    for letter in search.source() {
+        answers[*letter] += 1;
+    }
+    let sparse_sources = answers
+        .into_iter()
+        .filter(|(_, answers)| *answers > 0)
+        .map(|(letter, answers)| SparseSource {
+            letter,
+            answers,
+            candidates: search.candidate_count(letter),
+        })
+        .filter(|x| x.candidates < SPARSE_CANDIDATES * x.answers)
+        .collect();
+    let clauses = clauses(quote);
+    let trims = if clauses.len() < 2 {
+        vec![]
+    } else {
+        clauses
+            .into_iter()
+            .map(|clause| {
+                let letters = LetterSet::from_str(&clause);
+                let stop = |_| deadline.is_past();
+                let solvable = if letters.is_subset(search.quote()) {
+                    match search.exact_outcome(search.quote() - letters, budget, &stop) {
+                        ExactOutcome::Found(_) => Some(true),
+                        ExactOutcome::Infeasible => Some(false),
+                        ExactOutcome::Exhausted => None,
+                    }
+                } else {
+                    None
+                };
+                Trim { clause, solvable }
+            })
+            .collect()
+    };
+    Diagnosis {
+        failed_attempts,
+        leftovers: leftovers(failed_attempts, &leftover_counts),
+        sparse_sources,
+        imbalances: imbalances(search.quote(), shares),
+        trims,
+    }
+}
+
+/// Diagnoses a failed search, printing the report and writing it beside the puzzle.
+pub async fn explain_failure(
+    search: &Arc<Search>,
+    quote: &str,
+    pindex: usize,
+    budget: usize,
+    deadline: Deadline,
+) -> anyhow::Result<()> {
+    let table = FLAT_TRIE_TABLE.get().await.clone_error_static()?;
+    let shares = letter_shares(table.words());
+    let search = search.clone();
+    let quote = quote.to_string();
+    let diagnosis = tokio::task::spawn_blocking(move || {
+        diagnose(&search, &quote, &shares, budget, deadline)
+    })
+    .await?;
+    eprint!("puzzle={} answers failed\n{}", pindex, diagnosis);
+    diagnosis.write(pindex).await
+}
+
+#[test]
+fn test_diagnosis() {
+    let letter = |x: u8| Letter::new(x).unwrap();
+    let mut counts = LetterMap::<usize>::new();
+    counts[letter(b'q')] = 9;
+    counts[letter(b'x')] = 5;
+    counts[letter(b'e')] = 1;
+    let found = leftovers(10, &counts);
+    assert_eq!(
+        found,
+        vec![
+            Leftover {
+                letter: letter(b'q'),
+                rate: 0.9
+            },
+            Leftover {
+                letter: letter(b'x'),
+                rate: 0.5
+            },
+        ]
+    );
+    assert!(leftovers(0, &counts).is_empty());
+
+    let shares = LetterMap::<f64>::new().map(|_| 1.0 / 26.0);
+    let found = imbalances(LetterSet::from_str("qqqq abcdefghijklmnopq"), &shares);
+    assert_eq!(found.len(), 1);
+    assert_eq!((found[0].letter, found[0].count), (letter(b'q'), 5));
+
+    assert_eq!(
+        clauses("I came, I saw; I conquered. \u{2014} 1"),
+        vec!["I came", "I saw", "I conquered"]
+    );
+
+    let diagnosis = Diagnosis {
+        failed_attempts: 10,
+        leftovers: vec![Leftover {
+            letter: letter(b'q'),
+            rate: 0.9,
+        }],
+        trims: vec![
+            Trim {
+                clause: "I came".to_string(),
+                solvable: Some(true),
+            },
+            Trim {
+                clause: "I saw".to_string(),
+                solvable: Some(false),
+            },
+        ],
+        ..Diagnosis::default()
+    };
+    assert_eq!(
+        diagnosis.to_string(),
+        "failed attempts: 10\noften left over: Q (90%)\ndropping \"I came\" leaves a solvable quote\n"
+    );
+}
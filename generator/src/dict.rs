@@ -1,9 +1,13 @@
+use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet};
 use std::future::Future;
 use std::io;
 
 use crate::banned::BANNED_WORDS;
+use crate::turtle::db::TURTLE;
 use crate::{read_path_to_string, PACKAGE_PATH};
 use acrostic_core::letter::{Letter, LetterSet};
+use anyhow::anyhow;
 use arrayvec::{ArrayString, ArrayVec};
 use futures::future::Shared;
 use futures::FutureExt;
@@ -24,14 +28,61 @@ pub struct FlatWordBuilder {
     pub letter_vec: Vec<Letter>,
     pub letters: LetterSet,
     pub frequency: u64,
+    /// For phrases, the positions in `letter_vec` where each word after the first starts.
+    pub breaks: Vec<u8>,
 }
 
+impl FlatWordBuilder {
+    /// An entry for `word`, which may be a phrase with words separated by spaces.
+    pub fn new(word: &str, frequency: u64) -> Self {
+        let mut letter_vec = Vec::new();
+        let mut letters = LetterSet::new();
+        let mut breaks = Vec::new();
+        for part in word.split(' ') {
+            if !letter_vec.is_empty() {
+                breaks.push(letter_vec.len() as u8);
+            }
+            for c in any_ascii::any_ascii(part).bytes() {
+                if let Ok(letter) = Letter::new(c) {
+                    letter_vec.push(letter);
+                    letters[letter] += 1;
+                }
+            }
+        }
+        breaks.dedup();
+        breaks.retain(|x| (*x as usize) < letter_vec.len());
+        FlatWordBuilder {
+            word: word.to_string(),
+            letter_vec,
+            letters,
+            frequency,
+            breaks,
+        }
+    }
+}
+
+/// Wiktionary phrases with more words than this are left out of the dictionary.
+const MAX_PHRASE_WORDS: usize = 3;
+
+/// A phrase has no frequency of its own in the word list; it is taken as its rarest word's
+/// frequency divided by this.
+const PHRASE_DISCOUNT: u64 = 10;
+
 // pub static FLAT_WORDS: LazyMmap<FlatWord> =
 //     LazyMmap::<FlatWord>::new(|| PACKAGE_PATH.join("build/dict.dat"));
 
 pub static FLAT_WORDS: LazyLock<PersistentFile<Vec<FlatWordBuilder>>> =
     LazyLock::new(|| PersistentFile::new(&PACKAGE_PATH.join("build/dict.dat")));
 
+/// An archived dictionary of `(word, frequency)` pairs, leaked to live as long as [FLAT_WORDS].
+#[cfg(test)]
+pub fn test_dict(words: &[(&str, u64)]) -> &'static [FlatWord] {
+    let builders: Vec<FlatWordBuilder> =
+        words.iter().map(|(word, frequency)| FlatWordBuilder::new(word, *frequency)).collect();
+    let bytes = Box::leak(Box::new(rkyv::to_bytes::<_, 256>(&builders).unwrap()));
+    rkyv::check_archived_root::<Vec<FlatWordBuilder>>(&bytes[..]).unwrap()
+}
+
 #[tokio::test]
 async fn test_flat_word() {
     let words = FLAT_WORDS.get_static().await.unwrap();
@@ -41,37 +92,89 @@ async fn test_flat_word() {
     }
 }
 
-pub async fn build_dict() -> io::Result<()> {
+/// The estimated frequency of a lowercase phrase of two to [MAX_PHRASE_WORDS] words, or
+/// `None` if it is not such a phrase or any of its words is rare or banned.
+fn phrase_frequency(phrase: &str, frequencies: &HashMap<&str, u64>) -> Option<u64> {
+    let words: Vec<&str> = phrase.split(' ').collect();
+    if !(2..=MAX_PHRASE_WORDS).contains(&words.len()) {
+        return None;
+    }
+    let mut min = u64::MAX;
+    for word in words {
+        if word.is_empty() || !word.bytes().all(|x| x.is_ascii_lowercase()) {
+            return None;
+        }
+        if BANNED_WORDS.contains(word) {
+            return None;
+        }
+        min = min.min(*frequencies.get(word)?);
+    }
+    Some(min / PHRASE_DISCOUNT)
+}
+
+/// Multi-word written forms from the parsed dbnary data, such as "ice cream".
+async fn phrases(frequencies: &HashMap<&str, u64>) -> anyhow::Result<Vec<FlatWordBuilder>> {
+    if !tokio::fs::try_exists(PACKAGE_PATH.join("build/turtle.dat")).await? {
+        return Err(anyhow!(
+            "build/turtle.dat is missing; run `global turtle` first, since the dictionary's \
+             phrases come from it"
+        ));
+    }
+    let graph = TURTLE.get().await.clone_error_static()?;
+    let written_rep = graph
+        .get_index("http://www.w3.org/ns/lemon/ontolex#writtenRep")
+        .ok_or_else(|| anyhow!("no writtenRep predicate in build/turtle.dat"))?;
+    let mut seen = HashSet::new();
+    let mut phrases = vec![];
+    for (_, o) in graph.get_edges_by_predicate(written_rep) {
+        let name = graph.get_name(o);
+        if let Some(frequency) = phrase_frequency(name, frequencies) {
+            if seen.insert(name) {
+                phrases.push(FlatWordBuilder::new(name, frequency));
+            }
+        }
+    }
+    Ok(phrases)
+}
+
+pub async fn build_dict() -> anyhow::Result<()> {
     let contents = read_path_to_string(
         &PACKAGE_PATH.join("submodules/wikipedia-word-frequency/results/enwiki-2022-08-29.txt"),
     )
     .await?;
 
     let mut words = vec![];
+    let mut frequencies = HashMap::new();
     for line in contents.split("\n") {
         if line.is_empty() {
             continue;
         }
         let (word, freq) = line.split_once(" ").unwrap();
-        let freq: usize = freq.parse().unwrap();
-        let mut letter_vec = Vec::new();
-        let mut letters = LetterSet::new();
-        for c in any_ascii::any_ascii(&word).chars() {
-            if let Ok(letter) = Letter::new(c.try_into().unwrap()) {
-                letter_vec.push(letter);
-                letters[letter] += 1;
-            }
-        }
+        let freq: u64 = freq.parse().unwrap();
+        frequencies.insert(word, freq);
         if !BANNED_WORDS.contains(word) {
-            words.push(FlatWordBuilder {
-                word: (*word.to_string()).try_into().unwrap(),
-                letter_vec,
-                letters,
-                frequency: freq as u64,
-            });
+            words.push(FlatWordBuilder::new(word, freq));
         }
     }
+    words.extend(phrases(&frequencies).await?);
+    // Phrases are interleaved by frequency; the manifest gives them a quota of their own.
+    words.sort_by_key(|x| Reverse(x.frequency));
 
     FLAT_WORDS.set(&words).await?;
     Ok(())
 }
+
+#[test]
+fn test_phrases() {
+    let phrase = FlatWordBuilder::new("take a break", 1);
+    assert_eq!(phrase.letter_vec.len(), 10);
+    assert_eq!(phrase.breaks, vec![4, 5]);
+    assert_eq!(phrase.letters, LetterSet::from_str("takeabreak"));
+    assert!(FlatWordBuilder::new("netball", 1).breaks.is_empty());
+    let frequencies = HashMap::from([("ice", 5000), ("cream", 3000), ("take", 9000)]);
+    assert_eq!(phrase_frequency("ice cream", &frequencies), Some(300));
+    assert_eq!(phrase_frequency("ice", &frequencies), None);
+    assert_eq!(phrase_frequency("Ice cream", &frequencies), None);
+    assert_eq!(phrase_frequency("ice cream take ice", &frequencies), None);
+    assert_eq!(phrase_frequency("take a break", &frequencies), None);
+}
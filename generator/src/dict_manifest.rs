@@ -0,0 +1,201 @@
+//! Which dictionary words the tries are built from. A manifest is a TOML file such as
+//!
+//! ```toml
+//! min_letters = 6
+//! max_rank = 20000
+//! max_phrases = 2000
+//! banned = ["sheesh"]
+//! banned_files = ["data/banned_extra.txt"]
+//! allow = ["jukebox"]
+//! ```
+//!
+//! Trie tables are stored under `build/tries/<key>/`, where the key is a hash of the manifest
+//! and its banned files, so tables built from different manifests sit side by side. The key
+//! does not cover the dictionary, so loading a table checks its words against it instead.
+
+use std::collections::HashSet;
+use std::path::PathBuf;
+use std::sync::{LazyLock, OnceLock};
+
+use safe_once_async::async_lazy::AsyncLazy;
+use safe_once_async::detached::{spawn_transparent, JoinTransparent};
+use safe_once_async::sync::AsyncLazyLock;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::dict::FlatWord;
+use crate::{read_path_to_string, PACKAGE_PATH};
+
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DictManifest {
+    /// Leave out words shorter than this, unless allowed.
+    pub min_letters: usize,
+    /// Keep only this many of the most frequent single words passing the other filters. The
+    /// binary tries grow with the square of this plus `max_phrases`.
+    pub max_rank: usize,
+    /// Keep this many of the most frequent phrases on top of `max_rank` words. Phrase
+    /// frequencies are discounted estimates, so sharing the word quota would leave almost
+    /// all of them out.
+    pub max_phrases: usize,
+    /// Words never to use, on top of the built-in banned list.
+    pub banned: Vec<String>,
+    /// Files of further banned words, one per line, relative to the data directory.
+    pub banned_files: Vec<PathBuf>,
+    /// Words to include whatever their length or rank. Each must be in the dictionary.
+    pub allow: Vec<String>,
+}
+
+impl Default for DictManifest {
+    fn default() -> Self {
+        DictManifest {
+            min_letters: 6,
+            max_rank: 15000,
+            max_phrases: 1500,
+            banned: vec![],
+            banned_files: vec![],
+            allow: vec![],
+        }
+    }
+}
+
+impl DictManifest {
+    pub fn from_toml(contents: &str) -> anyhow::Result<Self> {
+        Ok(toml::from_str(contents)?)
+    }
+}
+
+/// The manifest given with `--dict-manifest`; without one, [DictManifest::default] is used.
+pub static DICT_MANIFEST_PATH: OnceLock<PathBuf> = OnceLock::new();
+
+pub static DICT_SELECTION: LazyLock<AsyncLazyLock<JoinTransparent<anyhow::Result<DictSelection>>>> =
+    LazyLock::new(|| {
+        AsyncLazy::new(spawn_transparent(async {
+            let manifest = match DICT_MANIFEST_PATH.get() {
+                Some(path) => DictManifest::from_toml(&read_path_to_string(path).await?)
+                    .map_err(|e| anyhow::anyhow!("{:?}: {}", path, e))?,
+                None => DictManifest::default(),
+            };
+            DictSelection::new(manifest).await
+        }))
+    });
+
+/// A manifest with its banned files read.
+#[derive(Debug)]
+pub struct DictSelection {
+    pub manifest: DictManifest,
+    banned: HashSet<String>,
+    /// Identifies the trie table built from this selection.
+    pub key: String,
+}
+
+impl DictSelection {
+    pub async fn new(manifest: DictManifest) -> anyhow::Result<Self> {
+        let mut hasher = Sha256::new();
+        hasher.update(serde_json::to_string(&manifest)?);
+        let mut banned: HashSet<String> = manifest.banned.iter().cloned().collect();
+        for file in &manifest.banned_files {
+            let contents = read_path_to_string(&PACKAGE_PATH.join(file)).await?;
+            hasher.update(&contents);
+            banned.extend(
+                contents
+                    .lines()
+                    .map(|x| x.trim().to_string())
+                    .filter(|x| !x.is_empty()),
+            );
+        }
+        let key = hex::encode(&hasher.finalize()[..8]);
+        Ok(DictSelection {
+            manifest,
+            banned,
+            key,
+        })
+    }
+    /// Whether the manifest bans `word`, by name or in one of its banned files.
+    pub fn is_banned(&self, word: &str) -> bool {
+        self.banned.contains(word)
+    }
+    /// Where the trie table for this selection is stored.
+    pub fn dir(&self) -> PathBuf {
+        PACKAGE_PATH.join("build/tries").join(&self.key)
+    }
+    /// The dictionary words and phrases the tries are built from, most frequent first,
+    /// followed by allowed words that did not make the cut.
+    pub fn words<'a>(&self, dict: &'a [FlatWord]) -> Vec<&'a FlatWord> {
+        let allow: HashSet<&str> = self.manifest.allow.iter().map(|x| x.as_str()).collect();
+        let allowed = |x: &&FlatWord| allow.contains(x.word.as_str());
+        let kept = |x: &&FlatWord| !self.is_banned(&x.word);
+        let (mut single, mut phrases) = (0, 0);
+        let within_quota = |x: &&FlatWord| {
+            let (count, quota) = if x.breaks.is_empty() {
+                (&mut single, self.manifest.max_rank)
+            } else {
+                (&mut phrases, self.manifest.max_phrases)
+            };
+            *count += 1;
+            *count <= quota
+        };
+        let mut words: Vec<&FlatWord> = dict
+            .iter()
+            .filter(kept)
+            .filter(|x| x.letters.count() >= self.manifest.min_letters)
+            .filter(within_quota)
+            .collect();
+        let included: HashSet<&str> = words.iter().map(|x| x.word.as_str()).collect();
+        let extra: Vec<&FlatWord> = dict
+            .iter()
+            .filter(|x| allowed(x) && kept(x) && !included.contains(x.word.as_str()))
+            .collect();
+        words.extend(extra);
+        words
+    }
+    /// Allowed words the dictionary lacks.
+    pub fn missing_allowed(&self, dict: &[FlatWord]) -> Vec<String> {
+        let found: HashSet<&str> = dict.iter().map(|x| x.word.as_str()).collect();
+        self.manifest
+            .allow
+            .iter()
+            .filter(|x| !found.contains(x.as_str()))
+            .cloned()
+            .collect()
+    }
+}
+
+#[tokio::test]
+async fn test_dict_selection() -> anyhow::Result<()> {
+    use crate::dict::test_dict;
+    let dict = test_dict(
+        &["crossword", "puzzle", "ice cream", "quote", "acrostic", "jukebox", "crop top"]
+            .map(|x| (x, 1)),
+    );
+    let words = |selection: &DictSelection| -> Vec<String> {
+        selection
+            .words(dict)
+            .into_iter()
+            .map(|x| x.word.to_string())
+            .collect()
+    };
+    let default = DictSelection::new(DictManifest::default()).await?;
+    assert_eq!(
+        words(&default),
+        vec!["crossword", "puzzle", "ice cream", "acrostic", "jukebox", "crop top"]
+    );
+
+    let manifest = DictManifest::from_toml(
+        "min_letters = 5\nmax_rank = 2\nmax_phrases = 1\nbanned = [\"puzzle\"]\n\
+         allow = [\"jukebox\", \"zzz\"]",
+    )?;
+    let selection = DictSelection::new(manifest).await?;
+    assert_eq!(
+        words(&selection),
+        vec!["crossword", "ice cream", "quote", "jukebox"]
+    );
+    assert_eq!(selection.missing_allowed(dict), vec!["zzz"]);
+    assert_ne!(selection.key, default.key);
+    assert_eq!(
+        default.key,
+        DictSelection::new(DictManifest::default()).await?.key
+    );
+    assert!(DictManifest::from_toml("max_words = 10").is_err());
+    Ok(())
+}
@@ -0,0 +1,535 @@
+//! Interactive editing of a puzzle's answers stage. The editor replaces one answer at a time,
+//! and the search refits the other answers so every quote letter stays used.
+
+use std::collections::HashMap;
+use std::io::Write;
+
+use acrostic_core::letter::{Letter, LetterSet};
+use anyhow::anyhow;
+use itertools::Itertools;
+use tokio::io::{AsyncBufReadExt, BufReader};
+
+use crate::cli::EditArgs;
+use crate::dict::FlatWord;
+use crate::pipeline::Stage;
+use crate::puzzle::{Clue, Puzzle, PuzzleFile};
+use crate::query::WordQuery;
+use crate::score::{default_scorer, difficulty_score, Familiarity};
+use crate::search::{to_clues, AnswerConstraints, Deadline, Search, Solution};
+use crate::trie_table::{FlatTrieTable, FLAT_TRIE_TABLE};
+use crate::util::lazy_async::CloneError;
+
+/// The most replacements `suggest` lists.
+const SUGGESTIONS: usize = 8;
+/// How many of the most frequent words that fit `suggest` tries to refit the puzzle around.
+const SUGGESTION_CANDIDATES: usize = 40;
+
+const HELP: &str = "\
+show          list the answers and the letters left over
+suggest N     list replacements for answer N that keep the puzzle solvable
+set N WORD    replace answer N, pin it, and refit the unpinned answers around it
+unpin N       let the search change answer N again
+repair        try again to use up the letters left over
+undo          take back the last set, unpin or repair
+save          write the answers stage, recomputing the indices
+quit          leave; `quit!` leaves without saving";
+
+/// A replacement for one answer, with the other answers refitted around it.
+pub struct Suggestion {
+    pub word: &'static FlatWord,
+    pub solution: Solution,
+}
+
+pub struct Editor {
+    search: Search,
+    query: WordQuery<'static>,
+    solution: Solution,
+    /// Words the search's scorer may have ruled out that the editor has held, by first letter
+    /// and letters: the answers the puzzle started with, and every word `set` placed.
+    placed: HashMap<(Letter, LetterSet), &'static FlatWord>,
+    /// Words `answers.toml` and `--exclude` rule out, which `set` refuses.
+    excluded: AnswerConstraints,
+    /// The solution and pins before each change, for `undo`.
+    history: Vec<(Solution, Vec<Option<&'static FlatWord>>)>,
+}
+
+impl Editor {
+    /// Starts from `answers`, one per source letter of `search`. `constraints` should be the
+    /// ones `search` was built with.
+    pub fn new(
+        search: Search,
+        table: &'static FlatTrieTable,
+        answers: &[&'static FlatWord],
+        constraints: &AnswerConstraints,
+        seed: u64,
+    ) -> anyhow::Result<Self> {
+        let solution = search
+            .solution(answers.iter().map(|x| x.letters).collect(), seed)
+            .ok_or_else(|| anyhow!("the answers do not fit in the quote"))?;
+        let placed = search
+            .source()
+            .iter()
+            .zip(answers)
+            .map(|(first, x)| ((*first, x.letters), *x))
+            .collect();
+        Ok(Editor {
+            search,
+            query: WordQuery::new(table),
+            solution,
+            placed,
+            excluded: AnswerConstraints {
+                pin: vec![],
+                exclude: constraints.exclude.clone(),
+            },
+            history: vec![],
+        })
+    }
+    fn word(&self, solution: &Solution, index: usize) -> anyhow::Result<&'static FlatWord> {
+        let letters = solution.words()[index];
+        let first = self.search.source()[index];
+        self.search
+            .pinned(index)
+            .filter(|x| x.letters == letters)
+            .or_else(|| self.placed.get(&(first, letters)).copied())
+            .or_else(|| self.search.word(index, letters))
+            .ok_or_else(|| {
+                anyhow!("no known word starting with {} for answer {}, {:?}", first, index, letters)
+            })
+    }
+    fn words(&self, solution: &Solution) -> anyhow::Result<Vec<&'static FlatWord>> {
+        (0..solution.words().len())
+            .map(|i| self.word(solution, i))
+            .collect()
+    }
+    pub fn answers(&self) -> anyhow::Result<Vec<&'static FlatWord>> {
+        self.words(&self.solution)
+    }
+    pub fn is_done(&self) -> bool {
+        self.search.is_done(&self.solution)
+    }
+    pub fn show(&self) -> anyhow::Result<String> {
+        let mut lines: Vec<String> = self
+            .answers()?
+            .iter()
+            .enumerate()
+            .map(|(i, x)| {
+                let pinned = if self.search.pinned(i).is_some() {
+                    "  (pinned)"
+                } else {
+                    ""
+                };
+                format!("{:>3} {} {}{}", i, self.search.source()[i], x.word, pinned)
+            })
+            .collect();
+        let remainder = self.solution.remainder();
+        if remainder.count() == 0 {
+            lines.push("no letters left over".to_string());
+        } else {
+            lines.push(format!("left over: {:?}", remainder));
+        }
+        Ok(lines.join("\n"))
+    }
+    /// The answers other than `skip` that differ between two solutions, as
+    /// `index: old -> new`.
+    fn changes(
+        &self,
+        before: &Solution,
+        after: &Solution,
+        skip: Option<usize>,
+    ) -> anyhow::Result<String> {
+        let (before, after) = (self.words(before)?, self.words(after)?);
+        Ok((0..before.len())
+            .filter(|i| Some(*i) != skip && before[*i].word != after[*i].word)
+            .map(|i| format!("{}: {} -> {}", i, before[i].word, after[i].word))
+            .join(", "))
+    }
+    fn pins(&self) -> Vec<Option<&'static FlatWord>> {
+        (0..self.solution.words().len())
+            .map(|i| self.search.pinned(i))
+            .collect()
+    }
+    fn restore_pins(&mut self, pins: &[Option<&'static FlatWord>]) {
+        for (i, pin) in pins.iter().enumerate() {
+            match pin {
+                Some(word) => self.search.pin(i, word).unwrap(),
+                None => self.search.unpin(i),
+            }
+        }
+    }
+    fn record(&mut self) {
+        let pins = self.pins();
+        self.history.push((self.solution.clone(), pins));
+    }
+    pub fn undo(&mut self) -> bool {
+        let Some((solution, pins)) = self.history.pop() else {
+            return false;
+        };
+        self.solution = solution;
+        self.restore_pins(&pins);
+        true
+    }
+    fn index(&self, s: &str) -> anyhow::Result<usize> {
+        let index: usize = s
+            .trim()
+            .parse()
+            .map_err(|_| anyhow!("expected an answer number, got {:?}", s))?;
+        if index >= self.solution.words().len() {
+            return Err(anyhow!(
+                "there are only {} answers",
+                self.solution.words().len()
+            ));
+        }
+        Ok(index)
+    }
+    /// The current solution with `word` pinned at `index` and the rest refitted, if that
+    /// uses every letter. Leaves the editor as it was.
+    fn refit(&mut self, index: usize, word: &'static FlatWord) -> Option<Solution> {
+        let pins = self.pins();
+        self.search.pin(index, word).ok()?;
+        let mut solution = self.solution.clone();
+        solution.set_word(index, word.letters);
+        let done = self.search.repair(&mut solution);
+        self.restore_pins(&pins);
+        done.then_some(solution)
+    }
+    /// Replacements for the answer at `index`, most frequent first, each with the refitted
+    /// answers it leads to. Words excluded for the puzzle are never suggested.
+    pub fn suggest(&mut self, index: usize) -> Vec<Suggestion> {
+        let current = self.solution.words()[index];
+        let free = self.solution.remainder() + current;
+        let candidates: Vec<_> = self
+            .query
+            .starting_with(self.search.source()[index], free)
+            .into_iter()
+            .filter(|x| x.letters != current && !self.excluded.is_excluded(&x.word))
+            .take(SUGGESTION_CANDIDATES)
+            .collect();
+        let mut found = vec![];
+        for word in candidates {
+            if let Some(solution) = self.refit(index, word) {
+                found.push(Suggestion { word, solution });
+                if found.len() == SUGGESTIONS {
+                    break;
+                }
+            }
+        }
+        found
+    }
+    /// Pins `word` at `index` and refits the unpinned answers around it. Returns whether
+    /// every letter ended up used; if not, the letters left over stay for the editor to place.
+    pub fn set(&mut self, index: usize, word: &'static FlatWord) -> anyhow::Result<bool> {
+        if self.excluded.is_excluded(&word.word) {
+            return Err(anyhow!("{} is excluded for this puzzle", word.word));
+        }
+        let free = self.solution.remainder() + self.solution.words()[index];
+        if !word.letters.is_subset(free) {
+            return Err(anyhow!(
+                "{} needs letters that are not free; answer {} can use {:?}",
+                word.word,
+                index,
+                free
+            ));
+        }
+        let pins = self.pins();
+        self.search.pin(index, word)?;
+        self.history.push((self.solution.clone(), pins));
+        self.placed.insert((self.search.source()[index], word.letters), word);
+        self.solution.set_word(index, word.letters);
+        Ok(self.search.repair(&mut self.solution))
+    }
+    /// Runs one command line, returning what to print.
+    pub fn command(&mut self, command: &str, rest: &str) -> anyhow::Result<String> {
+        let before = self.solution.clone();
+        let report = |editor: &Editor, done: bool| -> anyhow::Result<String> {
+            let mut report = editor.changes(&before, &editor.solution, None)?;
+            if !done {
+                report.push_str(
+                    "\ncould not use up the letters left over; try `suggest`, `repair` or `undo`",
+                );
+            }
+            Ok(format!("{}\n{}", report, editor.show()?)
+                .trim_start()
+                .to_string())
+        };
+        match command {
+            "" | "show" => self.show(),
+            "help" => Ok(HELP.to_string()),
+            "suggest" => {
+                let index = self.index(rest)?;
+                let found = self.suggest(index);
+                if found.is_empty() {
+                    return Ok("nothing that fits keeps the puzzle solvable".to_string());
+                }
+                let lines = found
+                    .iter()
+                    .map(|x| {
+                        let changes = self.changes(&self.solution, &x.solution, Some(index))?;
+                        Ok(format!("{:<20} {}", x.word.word, changes))
+                    })
+                    .collect::<anyhow::Result<Vec<_>>>()?;
+                Ok(lines.join("\n"))
+            }
+            "set" => {
+                let (index, word) = rest
+                    .split_once(char::is_whitespace)
+                    .ok_or_else(|| anyhow!("usage: set N WORD"))?;
+                let index = self.index(index)?;
+                let word = word.trim();
+                let word = self
+                    .search
+                    .dictionary_word(word)
+                    .ok_or_else(|| anyhow!("{:?} is not in the dictionary", word))?;
+                let done = self.set(index, word)?;
+                report(self, done)
+            }
+            "unpin" => {
+                let index = self.index(rest)?;
+                self.record();
+                self.search.unpin(index);
+                self.show()
+            }
+            "repair" => {
+                self.record();
+                let done = self.search.repair(&mut self.solution);
+                report(self, done)
+            }
+            "undo" => {
+                if !self.undo() {
+                    return Err(anyhow!("nothing to undo"));
+                }
+                self.show()
+            }
+            _ => Err(anyhow!("unknown command {:?}; try `help`", command)),
+        }
+    }
+}
+
+/// Writes the editor's answers to the answers stage. Clue text is kept for answers still in
+/// the puzzle, wherever they moved, and any clued stage becomes stale, as after `pick`.
+async fn save(puzzle: &mut Puzzle, pindex: usize, editor: &Editor) -> anyhow::Result<()> {
+    if !editor.is_done() {
+        return Err(anyhow!(
+            "the answers leave letters over, repeat or break the length limits; not saved"
+        ));
+    }
+    let table = FLAT_TRIE_TABLE.get().await.clone_error_static()?;
+    let words = editor.answers()?;
+    let mut old: HashMap<String, Clue> = puzzle
+        .clues
+        .clone()
+        .unwrap_or_default()
+        .into_iter()
+        .map(|x| (x.answer.clone(), x))
+        .collect();
+    let mut clues = to_clues(
+        &words,
+        puzzle.quote_letters.as_ref().unwrap(),
+        puzzle.source_letters.as_ref().unwrap(),
+        pindex as u64,
+    );
+    for clue in &mut clues {
+        if let Some(old) = old.remove(&clue.answer) {
+            clue.clue = old.clue;
+            clue.source = old.source;
+        }
+    }
+    puzzle.clues = Some(clues);
+    if let Some(difficulty) = &mut puzzle.difficulty {
+        difficulty.score = difficulty_score(&words, &Familiarity::new(table.words()));
+    }
+    puzzle.provenance_mut().search_seed = None;
+    puzzle.write(pindex, Stage::Answers).await?;
+    Ok(())
+}
+
+/// Reads editing commands from standard input until `quit` or the end of input.
+pub async fn edit_answers(args: &EditArgs) -> anyhow::Result<()> {
+    let mut puzzle = Puzzle::read(args.puzzle, Stage::Answers).await?;
+    let options = args.search.options();
+    let quote: LetterSet = puzzle
+        .quote_letters
+        .as_ref()
+        .unwrap()
+        .bytes()
+        .flat_map(Letter::new)
+        .collect();
+    let source: Vec<_> = puzzle
+        .source_letters
+        .as_ref()
+        .unwrap()
+        .bytes()
+        .flat_map(Letter::new)
+        .collect();
+    let table = FLAT_TRIE_TABLE.get().await.clone_error_static()?;
+    let scorer = default_scorer(table.words(), options.difficulty).await?;
+    let mut answers = AnswerConstraints::load(args.puzzle).await?;
+    answers.extend(&options.answers);
+    let lengths = options.lengths.clone();
+    let search = Search::new(
+        quote,
+        source,
+        &*scorer,
+        lengths,
+        &answers,
+        Deadline::after(None),
+    )
+    .await?;
+    let clues = puzzle
+        .clues
+        .clone()
+        .ok_or_else(|| anyhow!("puzzle {} has no answers", args.puzzle))?;
+    let words = clues
+        .iter()
+        .map(|x| {
+            search
+                .dictionary_word(&x.answer)
+                .ok_or_else(|| anyhow!("answer {:?} is not in the dictionary", x.answer))
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    let mut editor = Editor::new(search, table, &words, &answers, args.puzzle as u64)?;
+    let answer_words = |editor: &Editor| -> anyhow::Result<Vec<String>> {
+        Ok(editor
+            .answers()?
+            .iter()
+            .map(|x| x.word.to_string())
+            .collect())
+    };
+    let mut saved = answer_words(&editor)?;
+    println!("{}\ntype `help` for commands", editor.show()?);
+    let mut lines = BufReader::new(tokio::io::stdin()).lines();
+    loop {
+        print!("> ");
+        std::io::stdout().flush()?;
+        let Some(line) = lines.next_line().await? else {
+            break;
+        };
+        let line = line.trim();
+        let (command, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        let unsaved = answer_words(&editor)? != saved;
+        match command {
+            "save" => match save(&mut puzzle, args.puzzle, &editor).await {
+                Ok(()) => {
+                    saved = answer_words(&editor)?;
+                    println!("saved puzzle {}", args.puzzle);
+                }
+                Err(e) => println!("{}", e),
+            },
+            "quit" if unsaved => println!("unsaved changes; `save` first, or `quit!` to drop them"),
+            "quit" | "quit!" => return Ok(()),
+            _ => match editor.command(command, rest.trim()) {
+                Ok(output) => println!("{}", output),
+                Err(e) => println!("{}", e),
+            },
+        }
+    }
+    if answer_words(&editor)? != saved {
+        println!("end of input; unsaved changes dropped");
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_editor() -> anyhow::Result<()> {
+    use crate::dict_manifest::DictManifest;
+    use crate::search::LengthConstraints;
+    use crate::trie_table::{fixture, fixture_with};
+    let table: &'static FlatTrieTable = Box::leak(Box::new(
+        fixture(&["tone", "toe", "tot", "at", "ant"]).await?,
+    ));
+    let search = Search::with_table(
+        table,
+        LetterSet::from_str("toneat"),
+        vec![Letter::new(b't').unwrap(), Letter::new(b'a').unwrap()],
+        &Familiarity::new(table.words()),
+        LengthConstraints::default(),
+        &AnswerConstraints::default(),
+        Deadline::after(None),
+    )?;
+    let word = |x: &str| search.dictionary_word(x).unwrap();
+    let (tone, at, toe, tot) = (word("tone"), word("at"), word("toe"), word("tot"));
+    let mut editor = Editor::new(search, table, &[tone, at], &AnswerConstraints::default(), 0)?;
+    let answers = |editor: &Editor| -> Vec<String> {
+        editor
+            .answers()
+            .unwrap()
+            .iter()
+            .map(|x| x.word.to_string())
+            .collect()
+    };
+    assert!(editor.is_done());
+
+    let suggestions = editor.suggest(0);
+    assert_eq!(suggestions.len(), 1);
+    assert_eq!(suggestions[0].word.word.as_str(), "toe");
+    assert_eq!(answers(&editor), vec!["tone", "at"]);
+
+    assert!(editor.set(0, tot).is_err());
+    assert!(editor.set(0, toe)?);
+    assert_eq!(answers(&editor), vec!["toe", "ant"]);
+    assert!(editor.show()?.contains("toe  (pinned)"));
+    assert!(editor.undo());
+    assert_eq!(answers(&editor), vec!["tone", "at"]);
+    assert!(!editor.show()?.contains("pinned"));
+
+    let output = editor.command("suggest", "0")?;
+    assert!(output.starts_with("toe") && output.ends_with("1: at -> ant"), "{}", output);
+    let output = editor.command("set", "0 TOE")?;
+    assert!(
+        output.starts_with("0: tone -> toe, 1: at -> ant"),
+        "{}",
+        output
+    );
+    assert!(editor.command("set", "5 toe").is_err());
+    assert!(editor.command("frobnicate", "").is_err());
+
+    // "at" is too short for the tries, so the search never scores it; once set and unpinned
+    // the editor still knows it. Excluded words cannot be set.
+    let manifest = DictManifest { min_letters: 3, ..DictManifest::default() };
+    let table: &'static FlatTrieTable = Box::leak(Box::new(
+        fixture_with(&["tone", "toe", "at", "ant", "tote"], manifest).await?,
+    ));
+    let constraints = AnswerConstraints { pin: vec![], exclude: vec!["tote".to_string()] };
+    let search = Search::with_table(
+        table,
+        LetterSet::from_str("toneat"),
+        vec![Letter::new(b't').unwrap(), Letter::new(b'a').unwrap()],
+        &Familiarity::new(table.words()),
+        LengthConstraints::default(),
+        &constraints,
+        Deadline::after(None),
+    )?;
+    let word = |x: &str| search.dictionary_word(x).unwrap();
+    let (toe, ant) = (word("toe"), word("ant"));
+    let mut editor = Editor::new(search, table, &[toe, ant], &constraints, 0)?;
+    assert!(editor.command("set", "0 tote").is_err());
+    editor.command("set", "1 at")?;
+    assert_eq!(answers(&editor), vec!["tone", "at"]);
+    editor.command("unpin", "1")?;
+    assert_eq!(answers(&editor), vec!["tone", "at"]);
+    assert!(!editor.show()?.contains("pinned"));
+
+    // The tries still hold excluded words, but suggestions leave them out.
+    let table: &'static FlatTrieTable =
+        Box::leak(Box::new(fixture(&["toted", "tote", "an", "and"]).await?));
+    let editor_with = |constraints: &AnswerConstraints| -> anyhow::Result<Editor> {
+        let search = Search::with_table(
+            table,
+            LetterSet::from_str("totedan"),
+            vec![Letter::new(b't').unwrap(), Letter::new(b'a').unwrap()],
+            &Familiarity::new(table.words()),
+            LengthConstraints::default(),
+            constraints,
+            Deadline::after(None),
+        )?;
+        let word = |x: &str| search.dictionary_word(x).unwrap();
+        let answers = [word("toted"), word("an")];
+        Editor::new(search, table, &answers, constraints, 0)
+    };
+    let suggested = |mut editor: Editor| -> Vec<String> {
+        editor.suggest(0).iter().map(|x| x.word.word.to_string()).collect()
+    };
+    assert_eq!(suggested(editor_with(&AnswerConstraints::default())?), vec!["tote"]);
+    let constraints = AnswerConstraints { pin: vec![], exclude: vec!["tote".to_string()] };
+    assert!(suggested(editor_with(&constraints)?).is_empty());
+    Ok(())
+}
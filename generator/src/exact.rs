@@ -0,0 +1,231 @@
+//! A complete search for answer sets, as an alternative to the annealing in [crate::search].
+//!
+//! Answers sharing a first letter are interchangeable, so each group of them is chosen as an
+//! increasing sequence of letter sets. At each step the group with the fewest candidates is
+//! extended, and branches are cut when the remaining groups cannot possibly cover the
+//! remaining letters.
+
+use acrostic_core::letter::{Letter, LetterMap, LetterSet};
+
+/// The result of an exact search.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum ExactOutcome {
+    /// Answers in source-letter order.
+    Found(Vec<LetterSet>),
+    /// No set of dictionary words with the required first letters uses up the quote.
+    Infeasible,
+    /// The node budget ran out, or the search was stopped, before it finished.
+    Exhausted,
+}
+
+/// Lists the words that may fill a slot: `(first, remainder, placed)` gives every allowed
+/// word starting with `first` that fits in `remainder` next to the already `placed` words,
+/// best first.
+pub type Candidates<'a> = dyn Fn(Letter, LetterSet, &[LetterSet]) -> Vec<LetterSet> + 'a;
+
+struct Group {
+    first: Letter,
+    remaining: usize,
+    chosen: Vec<LetterSet>,
+}
+
+struct Exhausted;
+
+/// How often the search polls its `stop` function, in nodes.
+const STOP_CHECK_NODES: usize = 256;
+
+pub struct ExactSolver<'a> {
+    candidates: &'a Candidates<'a>,
+    budget: usize,
+    stop: &'a dyn Fn(usize) -> bool,
+    nodes: usize,
+    groups: Vec<Group>,
+    placed: Vec<LetterSet>,
+}
+
+impl<'a> ExactSolver<'a> {
+    /// Searches at most `budget` nodes, giving up early once `stop`, given the nodes searched
+    /// so far, returns true.
+    pub fn solve(
+        quote: LetterSet,
+        source: &[Letter],
+        candidates: &'a Candidates<'a>,
+        budget: usize,
+        stop: &'a dyn Fn(usize) -> bool,
+    ) -> ExactOutcome {
+        let mut counts = LetterMap::<usize>::new();
+        for first in source {
+            counts[*first] += 1;
+        }
+        let mut solver = ExactSolver {
+            candidates,
+            budget,
+            stop,
+            nodes: 0,
+            groups: counts
+                .into_iter()
+                .filter(|(_, count)| *count > 0)
+                .map(|(first, remaining)| Group {
+                    first,
+                    remaining,
+                    chosen: vec![],
+                })
+                .collect(),
+            placed: vec![],
+        };
+        match solver.search(quote) {
+            Err(Exhausted) => ExactOutcome::Exhausted,
+            Ok(false) => ExactOutcome::Infeasible,
+            Ok(true) => {
+                let mut chosen: LetterMap<Vec<LetterSet>> = LetterMap::new();
+                for group in solver.groups {
+                    chosen[group.first] = group.chosen;
+                }
+                ExactOutcome::Found(source.iter().map(|x| chosen[*x].pop().unwrap()).collect())
+            }
+        }
+    }
+    fn search(&mut self, remainder: LetterSet) -> Result<bool, Exhausted> {
+        self.nodes += 1;
+        let poll = self.nodes % STOP_CHECK_NODES == 1;
+        if self.nodes > self.budget || (poll && (self.stop)(self.nodes)) {
+            return Err(Exhausted);
+        }
+        if self.groups.iter().all(|x| x.remaining == 0) {
+            return Ok(remainder.count() == 0);
+        }
+        let mut options = vec![];
+        for (index, group) in self.groups.iter().enumerate() {
+            if group.remaining == 0 {
+                continue;
+            }
+            let mut found = (self.candidates)(group.first, remainder, &self.placed);
+            if let Some(last) = group.chosen.last() {
+                found.retain(|x| x > last);
+            }
+            if found.len() < group.remaining {
+                return Ok(false);
+            }
+            options.push((index, found));
+        }
+        if !Self::may_cover(remainder, &options, &self.groups) {
+            return Ok(false);
+        }
+        let (index, found) = options
+            .into_iter()
+            .min_by_key(|(_, found)| found.len())
+            .unwrap();
+        for word in found {
+            let group = &mut self.groups[index];
+            group.remaining -= 1;
+            group.chosen.push(word);
+            self.placed.push(word);
+            if self.search(remainder - word)? {
+                return Ok(true);
+            }
+            let group = &mut self.groups[index];
+            group.remaining += 1;
+            group.chosen.pop();
+            self.placed.pop();
+        }
+        Ok(false)
+    }
+    /// A relaxation of the rest of the search: each open slot may take its group's shortest or
+    /// longest candidate, and the most of each letter any candidate has.
+    fn may_cover(
+        remainder: LetterSet,
+        options: &[(usize, Vec<LetterSet>)],
+        groups: &[Group],
+    ) -> bool {
+        let mut min_len = 0;
+        let mut max_len = 0;
+        let mut most = LetterMap::<usize>::new();
+        for (index, found) in options {
+            let slots = groups[*index].remaining;
+            min_len += slots * found.iter().map(|x| x.count()).min().unwrap();
+            max_len += slots * found.iter().map(|x| x.count()).max().unwrap();
+            for letter in Letter::all() {
+                most[letter] += slots * found.iter().map(|x| x[letter] as usize).max().unwrap();
+            }
+        }
+        (min_len..=max_len).contains(&remainder.count())
+            && Letter::all().all(|letter| most[letter] >= remainder[letter] as usize)
+    }
+}
+
+#[test]
+fn test_exact_solver() {
+    use crate::trie::FlatTrie;
+    let words = ["tea", "toe", "ant", "not", "net", "ten", "oat"];
+    let mut tries = LetterMap::<Vec<(LetterSet, LetterSet)>>::new();
+    for word in words {
+        let first = Letter::new(word.as_bytes()[0]).unwrap();
+        tries[first].push((LetterSet::from_str(word), LetterSet::from_str(word)));
+    }
+    let tries = tries.map(|x| x.into_iter().collect::<Box<FlatTrie<LetterSet>>>());
+    let candidates = |first: Letter, remainder: LetterSet, _: &[LetterSet]| {
+        let mut found = vec![];
+        tries[first].search_all_subsets(remainder, &mut found);
+        found
+    };
+    let source = |x: &str| {
+        x.bytes()
+            .map(|x| Letter::new(x).unwrap())
+            .collect::<Vec<_>>()
+    };
+    let outcome = ExactSolver::solve(
+        LetterSet::from_str("nottoeant"),
+        &source("tan"),
+        &candidates,
+        1000,
+        &|_| false,
+    );
+    let ExactOutcome::Found(found) = outcome else {
+        panic!("{:?}", outcome);
+    };
+    assert_eq!(
+        found.iter().fold(LetterSet::new(), |a, b| a + *b),
+        LetterSet::from_str("nottoeant")
+    );
+    assert_eq!(
+        ExactSolver::solve(
+            LetterSet::from_str("nottoeanx"),
+            &source("tan"),
+            &candidates,
+            1000,
+            &|_| false
+        ),
+        ExactOutcome::Infeasible
+    );
+    // Two answers starting with T must be different words.
+    assert_eq!(
+        ExactSolver::solve(
+            LetterSet::from_str("teatea"),
+            &source("tt"),
+            &candidates,
+            1000,
+            &|_| false
+        ),
+        ExactOutcome::Infeasible
+    );
+    assert_eq!(
+        ExactSolver::solve(
+            LetterSet::from_str("nottoeant"),
+            &source("tan"),
+            &candidates,
+            1,
+            &|_| false
+        ),
+        ExactOutcome::Exhausted
+    );
+    assert_eq!(
+        ExactSolver::solve(
+            LetterSet::from_str("nottoeant"),
+            &source("tan"),
+            &candidates,
+            1000,
+            &|_| true
+        ),
+        ExactOutcome::Exhausted
+    );
+}
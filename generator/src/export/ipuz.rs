@@ -0,0 +1,236 @@
+use std::collections::BTreeMap;
+
+use acrostic_core::puzzle::{Clue, Puzzle, PuzzleState};
+use anyhow::anyhow;
+use serde::{Deserialize, Serialize};
+
+use crate::export::{clue_label, Acrostic, QUOTE_WIDTH};
+
+pub const VERSION: &str = "http://ipuz.org/v2";
+pub const KIND: &str = "http://ipuz.org/acrostic#1";
+const BLOCK: &str = "#";
+const CLUES: &str = "Clues";
+/// Separates the quote from its source in `explanation`.
+const SOURCE_SEPARATOR: &str = "\n\u{2014} ";
+
+#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+pub struct Ipuz {
+    pub version: String,
+    pub kind: Vec<String>,
+    pub title: String,
+    /// The quote and its source, shown once the puzzle is solved.
+    pub explanation: String,
+    pub dimensions: Dimensions,
+    /// `null` past the end of the quote.
+    pub puzzle: Vec<Vec<Option<Cell>>>,
+    pub solution: Vec<Vec<Option<String>>>,
+    pub clues: BTreeMap<String, Vec<IpuzClue>>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+pub struct Dimensions {
+    pub width: usize,
+    pub height: usize,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+#[serde(untagged)]
+pub enum Cell {
+    /// Always [BLOCK]; quote spaces.
+    Block(String),
+    Labeled(LabeledCell),
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+pub struct LabeledCell {
+    /// The cell number, or 0 for unnumbered cells.
+    pub cell: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub style: Option<Style>,
+    /// Prefilled content for digits and punctuation.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub value: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+pub struct Style {
+    /// Corner labels, e.g. `{"TR": "B"}` for a cell filled by clue B.
+    pub mark: BTreeMap<String, String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+pub struct IpuzClue {
+    pub number: String,
+    pub clue: String,
+    pub answer: String,
+    /// `[column, row]`, counting from `[1, 1]` at the top left.
+    pub cells: Vec<[usize; 2]>,
+}
+
+fn position(index: usize) -> [usize; 2] {
+    [index % QUOTE_WIDTH + 1, index / QUOTE_WIDTH + 1]
+}
+
+pub fn from_acrostic(acrostic: &Acrostic, title: &str) -> Ipuz {
+    let rows = acrostic.rows();
+    let mut puzzle = vec![vec![None; QUOTE_WIDTH]; rows];
+    let mut solution = vec![vec![None; QUOTE_WIDTH]; rows];
+    for (i, c) in acrostic.cells.iter().enumerate() {
+        let [x, y] = position(i);
+        let (cell, value) = if *c == ' ' {
+            (Cell::Block(BLOCK.to_string()), BLOCK.to_string())
+        } else if let (Some(number), Some(owner)) = (acrostic.numbers[i], acrostic.owners[i]) {
+            let mark = BTreeMap::from([("TR".to_string(), clue_label(owner))]);
+            let cell = LabeledCell {
+                cell: number,
+                style: Some(Style { mark }),
+                value: None,
+            };
+            (Cell::Labeled(cell), c.to_string())
+        } else {
+            let cell = LabeledCell {
+                cell: 0,
+                style: None,
+                value: Some(c.to_string()),
+            };
+            (Cell::Labeled(cell), c.to_string())
+        };
+        puzzle[y - 1][x - 1] = Some(cell);
+        solution[y - 1][x - 1] = Some(value);
+    }
+    let clues = acrostic
+        .clues
+        .iter()
+        .enumerate()
+        .map(|(j, clue)| IpuzClue {
+            number: clue_label(j),
+            clue: clue.clue.clone().unwrap_or_default(),
+            answer: clue.answer.clone(),
+            cells: clue.indices.iter().map(|i| position(*i)).collect(),
+        })
+        .collect();
+    Ipuz {
+        version: VERSION.to_string(),
+        kind: vec![KIND.to_string()],
+        title: title.to_string(),
+        explanation: format!(
+            "{}{}{}",
+            acrostic.puzzle.quote, SOURCE_SEPARATOR, acrostic.puzzle.source
+        ),
+        dimensions: Dimensions {
+            width: QUOTE_WIDTH,
+            height: rows,
+        },
+        puzzle,
+        solution,
+        clues: BTreeMap::from([(CLUES.to_string(), clues)]),
+    }
+}
+
+impl Ipuz {
+    /// The solution letter at `[column, row]`, counting from `[1, 1]`.
+    fn letter_at(&self, [x, y]: [usize; 2]) -> anyhow::Result<char> {
+        let value = self
+            .solution
+            .get(y.wrapping_sub(1))
+            .and_then(|row| row.get(x.wrapping_sub(1)))
+            .cloned()
+            .flatten()
+            .ok_or_else(|| anyhow!("no solution at {:?}", [x, y]))?;
+        let mut chars = value.chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) => Ok(c.to_ascii_uppercase()),
+            _ => Err(anyhow!("cell {:?} holds {:?}, not one character", [x, y], value)),
+        }
+    }
+    pub fn check_kind(&self) -> anyhow::Result<()> {
+        if !self.kind.iter().any(|x| x == KIND) {
+            return Err(anyhow!("not an ipuz acrostic: {:?}", self.kind));
+        }
+        Ok(())
+    }
+    /// The quote cells in reading order, with blocks as spaces.
+    pub fn quote_letters(&self) -> anyhow::Result<String> {
+        let mut quote_letters = String::new();
+        for (y, row) in self.puzzle.iter().enumerate() {
+            for (x, cell) in row.iter().enumerate() {
+                match cell {
+                    None => {}
+                    Some(Cell::Block(_)) => quote_letters.push(' '),
+                    Some(Cell::Labeled(_)) => quote_letters.push(self.letter_at([x + 1, y + 1])?),
+                }
+            }
+        }
+        Ok(quote_letters)
+    }
+    /// Splits `explanation` into the quote and its source.
+    pub fn quote_and_source(&self) -> (&str, &str) {
+        self.explanation
+            .rsplit_once(SOURCE_SEPARATOR)
+            .unwrap_or((&self.explanation, ""))
+    }
+    pub fn clues(&self) -> &[IpuzClue] {
+        self.clues.get(CLUES).map_or(&[], |x| x)
+    }
+}
+
+/// Rebuilds a clued puzzle from an ipuz acrostic laid out like [from_acrostic]'s output.
+pub fn to_puzzle(ipuz: &Ipuz) -> anyhow::Result<Puzzle> {
+    ipuz.check_kind()?;
+    let width = ipuz.dimensions.width;
+    let quote_letters = ipuz.quote_letters()?;
+    let mut clues = vec![];
+    for clue in ipuz.clues() {
+        let answer_letters = clue
+            .cells
+            .iter()
+            .map(|x| ipuz.letter_at(*x))
+            .collect::<anyhow::Result<String>>()?;
+        clues.push(Clue {
+            clue: Some(clue.clue.clone()).filter(|x| !x.is_empty()),
+            answer: clue.answer.clone(),
+            answer_letters,
+            indices: clue.cells.iter().map(|[x, y]| (y - 1) * width + (x - 1)).collect(),
+            source: None,
+            breaks: Clue::breaks_in(&clue.answer),
+        });
+    }
+    let (quote, source) = ipuz.quote_and_source();
+    let mut puzzle = Puzzle::new(quote.to_string(), source.to_string());
+    puzzle.source_letters = Some(
+        clues
+            .iter()
+            .filter_map(|x| x.answer_letters.chars().next())
+            .collect(),
+    );
+    puzzle.quote_letters = Some(quote_letters);
+    puzzle.state = if clues.iter().all(|x| x.clue.is_some()) {
+        PuzzleState::Clued
+    } else {
+        PuzzleState::Answered
+    };
+    puzzle.clues = Some(clues);
+    puzzle.validate()?;
+    Ok(puzzle)
+}
+
+#[test]
+fn test_ipuz_round_trip() {
+    for (pindex, puzzle) in super::clued_puzzles() {
+        let acrostic = Acrostic::new(&puzzle).unwrap();
+        let ipuz = from_acrostic(&acrostic, "Test");
+        let parsed: Ipuz = serde_json::from_str(&serde_json::to_string(&ipuz).unwrap()).unwrap();
+        assert_eq!(parsed, ipuz, "puzzle {}", pindex);
+        let rebuilt = to_puzzle(&parsed).unwrap();
+        assert_eq!(rebuilt.quote, puzzle.quote);
+        assert_eq!(rebuilt.source, puzzle.source);
+        assert_eq!(rebuilt.quote_letters, puzzle.quote_letters);
+        assert_eq!(rebuilt.source_letters, puzzle.source_letters);
+        for (a, b) in rebuilt.clues.unwrap().iter().zip(puzzle.clues.as_ref().unwrap()) {
+            assert_eq!(
+                (&a.clue, &a.answer, &a.answer_letters, &a.indices),
+                (&b.clue, &b.answer, &b.answer_letters, &b.indices)
+            );
+        }
+    }
+}
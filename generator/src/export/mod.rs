@@ -0,0 +1,152 @@
+use std::path::PathBuf;
+
+use acrostic_core::puzzle::{Clue, Puzzle, PuzzleState};
+use anyhow::anyhow;
+use clap::ValueEnum;
+use tokio::fs::create_dir_all;
+
+use crate::cli::ExportArgs;
+use crate::pipeline::Stage;
+use crate::puzzle::PuzzleFile;
+use crate::report::{Outcome, Report};
+use crate::{write_path, PACKAGE_PATH};
+
+pub mod ipuz;
+pub mod puz;
+
+#[derive(ValueEnum, Copy, Clone, Debug, Eq, PartialEq)]
+pub enum ExportFormat {
+    /// Across Lite binary.
+    Puz,
+    /// ipuz JSON with the acrostic kind.
+    Ipuz,
+}
+
+impl ExportFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            ExportFormat::Puz => "puz",
+            ExportFormat::Ipuz => "ipuz",
+        }
+    }
+}
+
+/// Width of the quote grid. Cell `i` of `quote_letters` is at row `i / width`, column `i % width`.
+pub const QUOTE_WIDTH: usize = 15;
+
+/// The label of the `i`th clue: `A` to `Z`, then `AA`, `BB` and so on.
+pub fn clue_label(i: usize) -> String {
+    let letter = (b'A' + (i % 26) as u8) as char;
+    letter.to_string().repeat(i / 26 + 1)
+}
+
+/// A clued puzzle, with the cell numbering solvers see.
+pub struct Acrostic<'a> {
+    pub puzzle: &'a Puzzle,
+    pub cells: Vec<char>,
+    pub clues: &'a [Clue],
+    /// For each cell, its 1-based number among the letter cells.
+    pub numbers: Vec<Option<usize>>,
+    /// For each cell, the clue whose answer fills it.
+    pub owners: Vec<Option<usize>>,
+}
+
+impl<'a> Acrostic<'a> {
+    pub fn new(puzzle: &'a Puzzle) -> anyhow::Result<Self> {
+        if puzzle.state != PuzzleState::Clued {
+            return Err(anyhow!("puzzle is {:?}, not clued", puzzle.state));
+        }
+        puzzle.validate()?;
+        let cells: Vec<char> = puzzle.quote_letters.as_ref().unwrap().chars().collect();
+        let clues = puzzle.clues.as_ref().unwrap();
+        let mut next = 0;
+        let numbers = cells
+            .iter()
+            .map(|x| {
+                x.is_ascii_uppercase().then(|| {
+                    next += 1;
+                    next
+                })
+            })
+            .collect();
+        let mut owners = vec![None; cells.len()];
+        for (i, clue) in clues.iter().enumerate() {
+            for index in &clue.indices {
+                owners[*index] = Some(i);
+            }
+        }
+        Ok(Acrostic {
+            puzzle,
+            cells,
+            clues,
+            numbers,
+            owners,
+        })
+    }
+    pub fn rows(&self) -> usize {
+        self.cells.len().div_ceil(QUOTE_WIDTH)
+    }
+    /// How a cell is described in clue text, e.g. `B12`.
+    pub fn cell_name(&self, index: usize) -> String {
+        match (self.owners[index], self.numbers[index]) {
+            (Some(owner), Some(number)) => format!("{}{}", clue_label(owner), number),
+            _ => self.cells[index].to_string(),
+        }
+    }
+}
+
+fn export_path(pindex: usize, format: ExportFormat) -> PathBuf {
+    PACKAGE_PATH
+        .join("build/export")
+        .join(format!("puzzle{}.{}", pindex, format.extension()))
+}
+
+pub async fn export_puzzle(pindex: usize, format: ExportFormat) -> anyhow::Result<()> {
+    let puzzle = Puzzle::read(pindex, Stage::Chat).await?;
+    let acrostic = Acrostic::new(&puzzle)?;
+    let title = format!("Acrostic {}", pindex);
+    let bytes = match format {
+        ExportFormat::Puz => puz::from_acrostic(&acrostic, &title).to_bytes(),
+        ExportFormat::Ipuz => serde_json::to_vec_pretty(&ipuz::from_acrostic(&acrostic, &title))?,
+    };
+    write_path(&export_path(pindex, format), &bytes).await?;
+    Ok(())
+}
+
+/// Writes clued puzzles to `build/export/`.
+pub async fn export_puzzles(args: &ExportArgs) -> anyhow::Result<()> {
+    create_dir_all(PACKAGE_PATH.join("build/export")).await?;
+    let mut report = Report::new();
+    for pindex in args.puzzle_indices() {
+        report.push(pindex, Outcome::from_result(export_puzzle(pindex, args.format).await));
+    }
+    report.print_summary();
+    if report.failures() > 0 {
+        return Err(anyhow!("{} puzzles failed", report.failures()));
+    }
+    Ok(())
+}
+
+/// Every clued puzzle under `PUZZLES_PATH`, for round-trip tests.
+#[cfg(test)]
+pub(crate) fn clued_puzzles() -> Vec<(usize, Puzzle)> {
+    let mut puzzles = vec![];
+    for entry in std::fs::read_dir(&*crate::PUZZLES_PATH).into_iter().flatten() {
+        let entry = entry.unwrap();
+        let Some(pindex) = entry.file_name().to_str().and_then(|x| x.parse().ok()) else {
+            continue;
+        };
+        if let Ok(input) = std::fs::read_to_string(entry.path().join(Stage::Chat.output())) {
+            puzzles.push((pindex, Puzzle::from_json(&input).unwrap()));
+        }
+    }
+    puzzles
+}
+
+#[test]
+fn test_clue_label() {
+    assert_eq!(clue_label(0), "A");
+    assert_eq!(clue_label(25), "Z");
+    assert_eq!(clue_label(26), "AA");
+    assert_eq!(clue_label(27), "BB");
+}
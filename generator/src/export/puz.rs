@@ -0,0 +1,289 @@
+use any_ascii::any_ascii;
+use anyhow::anyhow;
+use itertools::Itertools;
+
+use crate::export::{clue_label, Acrostic, QUOTE_WIDTH};
+
+const MAGIC: &[u8; 12] = b"ACROSS&DOWN\0";
+const VERSION: &[u8; 4] = b"1.3\0";
+const HEADER_LEN: usize = 0x34;
+const BLACK: u8 = b'.';
+const EMPTY: u8 = b'-';
+
+/// An unscrambled Across Lite file. Strings hold only characters up to U+00FF, which are
+/// written as ISO-8859-1.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct PuzFile {
+    pub width: u8,
+    pub height: u8,
+    /// Row-major, `.` for black squares.
+    pub solution: Vec<u8>,
+    /// The solver's progress: `-` for empty squares.
+    pub grid: Vec<u8>,
+    pub title: String,
+    pub author: String,
+    pub copyright: String,
+    /// Across and down clues in the order Across Lite numbers them.
+    pub clues: Vec<String>,
+    pub notes: String,
+}
+
+fn cksum_region(data: &[u8], mut cksum: u16) -> u16 {
+    for &b in data {
+        cksum = if cksum & 1 != 0 {
+            (cksum >> 1) | 0x8000
+        } else {
+            cksum >> 1
+        };
+        cksum = cksum.wrapping_add(b as u16);
+    }
+    cksum
+}
+
+/// Replaces characters that ISO-8859-1 cannot encode with ASCII approximations.
+fn to_latin1(x: &str) -> String {
+    x.chars()
+        .map(|c| {
+            if (c as u32) < 0x100 {
+                c.to_string()
+            } else {
+                any_ascii(&c.to_string())
+            }
+        })
+        .collect()
+}
+
+fn encode(x: &str) -> Vec<u8> {
+    x.chars().map(|c| c as u32 as u8).collect()
+}
+
+fn decode(x: &[u8]) -> String {
+    x.iter().map(|b| *b as char).collect()
+}
+
+impl PuzFile {
+    fn cib(&self) -> [u8; 8] {
+        let [lo, hi] = (self.clues.len() as u16).to_le_bytes();
+        // The bitmask is always 1; the scrambled tag is 0.
+        [self.width, self.height, lo, hi, 1, 0, 0, 0]
+    }
+    fn cksum_strings(&self, mut cksum: u16) -> u16 {
+        for s in [&self.title, &self.author, &self.copyright] {
+            if !s.is_empty() {
+                cksum = cksum_region(&encode(s), cksum);
+                cksum = cksum_region(&[0], cksum);
+            }
+        }
+        for clue in &self.clues {
+            cksum = cksum_region(&encode(clue), cksum);
+        }
+        if !self.notes.is_empty() {
+            cksum = cksum_region(&encode(&self.notes), cksum);
+            cksum = cksum_region(&[0], cksum);
+        }
+        cksum
+    }
+    fn checksums(&self) -> (u16, u16, [u8; 4], [u8; 4]) {
+        let cib = cksum_region(&self.cib(), 0);
+        let mut file = cib;
+        file = cksum_region(&self.solution, file);
+        file = cksum_region(&self.grid, file);
+        file = self.cksum_strings(file);
+        let parts = [
+            cib,
+            cksum_region(&self.solution, 0),
+            cksum_region(&self.grid, 0),
+            self.cksum_strings(0),
+        ];
+        let mut low = [0; 4];
+        let mut high = [0; 4];
+        for (i, part) in parts.iter().enumerate() {
+            low[i] = b"ICHE"[i] ^ (*part as u8);
+            high[i] = b"ATED"[i] ^ ((*part >> 8) as u8);
+        }
+        (file, cib, low, high)
+    }
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let (file, cib, low, high) = self.checksums();
+        let mut out = Vec::with_capacity(HEADER_LEN + 2 * self.solution.len());
+        out.extend_from_slice(&file.to_le_bytes());
+        out.extend_from_slice(MAGIC);
+        out.extend_from_slice(&cib.to_le_bytes());
+        out.extend_from_slice(&low);
+        out.extend_from_slice(&high);
+        out.extend_from_slice(VERSION);
+        out.extend_from_slice(&[0; 2]);
+        // Scrambled checksum.
+        out.extend_from_slice(&[0; 2]);
+        out.extend_from_slice(&[0; 12]);
+        out.extend_from_slice(&self.cib());
+        assert_eq!(out.len(), HEADER_LEN);
+        out.extend_from_slice(&self.solution);
+        out.extend_from_slice(&self.grid);
+        for s in [&self.title, &self.author, &self.copyright]
+            .into_iter()
+            .chain(&self.clues)
+            .chain([&self.notes])
+        {
+            out.extend_from_slice(&encode(s));
+            out.push(0);
+        }
+        out
+    }
+    /// Parses an unscrambled file, checking every checksum.
+    pub fn parse(bytes: &[u8]) -> anyhow::Result<Self> {
+        if bytes.len() < HEADER_LEN || &bytes[0x02..0x0E] != MAGIC {
+            return Err(anyhow!("not an Across Lite file"));
+        }
+        let u16_at = |i: usize| u16::from_le_bytes([bytes[i], bytes[i + 1]]);
+        let width = bytes[0x2C];
+        let height = bytes[0x2D];
+        let clue_count = u16_at(0x2E) as usize;
+        if u16_at(0x32) != 0 {
+            return Err(anyhow!("scrambled files are not supported"));
+        }
+        let cells = width as usize * height as usize;
+        let grids_end = HEADER_LEN + 2 * cells;
+        if bytes.len() < grids_end {
+            return Err(anyhow!("file ends inside the grid"));
+        }
+        let mut strings = bytes[grids_end..].split(|x| *x == 0).map(decode);
+        let mut next_string = || {
+            strings
+                .next()
+                .ok_or_else(|| anyhow!("file ends inside the strings"))
+        };
+        let title = next_string()?;
+        let author = next_string()?;
+        let copyright = next_string()?;
+        let clues = (0..clue_count).map(|_| next_string()).collect::<anyhow::Result<_>>()?;
+        let notes = next_string().unwrap_or_default();
+        let puz = PuzFile {
+            width,
+            height,
+            solution: bytes[HEADER_LEN..HEADER_LEN + cells].to_vec(),
+            grid: bytes[HEADER_LEN + cells..grids_end].to_vec(),
+            title,
+            author,
+            copyright,
+            clues,
+            notes,
+        };
+        let (file, cib, low, high) = puz.checksums();
+        if (file, cib) != (u16_at(0x00), u16_at(0x0E))
+            || low[..] != bytes[0x10..0x14]
+            || high[..] != bytes[0x14..0x18]
+        {
+            return Err(anyhow!("bad checksum"));
+        }
+        Ok(puz)
+    }
+    pub fn is_black(&self, x: usize, y: usize) -> bool {
+        x >= self.width as usize
+            || y >= self.height as usize
+            || self.solution[y * self.width as usize + x] == BLACK
+    }
+    /// The cells of each numbered entry, across entries before down entries at the same number.
+    pub fn entries(&self) -> Vec<Vec<(usize, usize)>> {
+        let mut entries = vec![];
+        for y in 0..self.height as usize {
+            for x in 0..self.width as usize {
+                if self.is_black(x, y) {
+                    continue;
+                }
+                if (x == 0 || self.is_black(x - 1, y)) && !self.is_black(x + 1, y) {
+                    let across = (x..).take_while(|x| !self.is_black(*x, y));
+                    entries.push(across.map(|x| (x, y)).collect());
+                }
+                if (y == 0 || self.is_black(x, y - 1)) && !self.is_black(x, y + 1) {
+                    let down = (y..).take_while(|y| !self.is_black(x, *y));
+                    entries.push(down.map(|y| (x, y)).collect());
+                }
+            }
+        }
+        entries
+    }
+}
+
+/// Lays the quote out on every other row, so no down entries form, followed by one row per
+/// answer. Quote entries are clued with the answer letter and number of each of their cells.
+pub fn from_acrostic(acrostic: &Acrostic, title: &str) -> PuzFile {
+    let quote_rows = acrostic.rows();
+    let longest = acrostic.clues.iter().map(|x| x.answer_letters.len()).max().unwrap_or(0);
+    let width = QUOTE_WIDTH.max(longest);
+    let height = 2 * (quote_rows + acrostic.clues.len()) - 1;
+    let mut solution = vec![BLACK; width * height];
+    for (i, c) in acrostic.cells.iter().enumerate() {
+        if c.is_ascii_uppercase() || c.is_ascii_digit() {
+            solution[2 * (i / QUOTE_WIDTH) * width + i % QUOTE_WIDTH] = *c as u8;
+        }
+    }
+    for (j, clue) in acrostic.clues.iter().enumerate() {
+        let row = 2 * (quote_rows + j);
+        solution[row * width..][..clue.answer_letters.len()]
+            .copy_from_slice(clue.answer_letters.as_bytes());
+    }
+    let grid = solution
+        .iter()
+        .map(|x| if *x == BLACK { BLACK } else { EMPTY })
+        .collect();
+    let mut puz = PuzFile {
+        width: width as u8,
+        height: height as u8,
+        solution,
+        grid,
+        title: to_latin1(title),
+        author: String::new(),
+        copyright: String::new(),
+        clues: vec![],
+        notes: "Each numbered square of the quotation is filled by the answer with the \
+                matching letter. The first letters of the answers spell the author and source."
+            .to_string(),
+    };
+    puz.clues = puz
+        .entries()
+        .iter()
+        .map(|entry| {
+            let (_, y) = entry[0];
+            if y < 2 * quote_rows {
+                let cells = entry.iter().map(|(x, y)| acrostic.cell_name(y / 2 * QUOTE_WIDTH + x));
+                format!("Quotation: {}", cells.format(" "))
+            } else {
+                let j = (y - 2 * quote_rows) / 2;
+                let clue = &acrostic.clues[j];
+                let numbers = clue.indices.iter().map(|i| acrostic.numbers[*i].unwrap());
+                format!(
+                    "{}. {} ({})",
+                    clue_label(j),
+                    to_latin1(clue.clue.as_deref().unwrap_or_default()),
+                    numbers.format(" ")
+                )
+            }
+        })
+        .collect();
+    puz
+}
+
+#[test]
+fn test_cksum_region() {
+    assert_eq!(cksum_region(b"", 0), 0);
+    assert_eq!(cksum_region(b"\x01", 0), 1);
+    // 1 rotates to 0x8000 before adding 2.
+    assert_eq!(cksum_region(b"\x01\x02", 0), 0x8002);
+}
+
+#[test]
+fn test_puz_round_trip() {
+    for (pindex, puzzle) in super::clued_puzzles() {
+        let acrostic = Acrostic::new(&puzzle).unwrap();
+        let puz = from_acrostic(&acrostic, "Test");
+        let parsed = PuzFile::parse(&puz.to_bytes()).unwrap();
+        assert_eq!(parsed, puz, "puzzle {}", pindex);
+        assert_eq!(parsed.clues.len(), parsed.entries().len());
+        for (i, c) in acrostic.cells.iter().enumerate() {
+            if c.is_ascii_uppercase() {
+                assert!(!parsed.is_black(i % QUOTE_WIDTH, 2 * (i / QUOTE_WIDTH)));
+            }
+        }
+    }
+}
@@ -0,0 +1,252 @@
+//! Bringing hand-made acrostics into the pipeline at the answers stage, so they can be clued
+//! and validated like generated ones.
+//!
+//! Besides ipuz acrostics, a plain text format is accepted: the quote on the first line, the
+//! source on the second, then one answer per line in source-letter order. Blank lines and
+//! lines starting with `#` are ignored.
+
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+use std::panic::catch_unwind;
+use std::path::Path;
+
+use acrostic_core::letter::{Letter, LetterMap};
+use anyhow::anyhow;
+
+use crate::add_letters::quote_to_cells;
+use crate::cli::ImportArgs;
+use crate::export::ipuz::Ipuz;
+use crate::pipeline::Stage;
+use crate::puzzle::{Clue, Puzzle, PuzzleFile};
+use crate::read_path_to_string;
+use crate::string::LetterString;
+
+/// Why the answers cannot be placed in the quote.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum ImportError {
+    AnswerCount { answers: usize, source_letters: usize },
+    FirstLetter { answer: String, expected: char },
+    /// Letters left over on either side once every answer letter is matched to a quote cell.
+    Unbalanced { unused_quote: String, missing_from_quote: String },
+}
+
+impl Display for ImportError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            ImportError::AnswerCount { answers, source_letters } => write!(
+                f,
+                "{} answers, but the source has {} letters",
+                answers, source_letters
+            ),
+            ImportError::FirstLetter { answer, expected } => {
+                write!(f, "answer {:?} should start with {:?}", answer, expected)
+            }
+            ImportError::Unbalanced { unused_quote, missing_from_quote } => write!(
+                f,
+                "letters don't balance: quote letters {:?} are unused, answer letters {:?} are not in the quote",
+                unused_quote, missing_from_quote
+            ),
+        }
+    }
+}
+
+impl Error for ImportError {}
+
+/// The parts of an acrostic an editor supplies; clues are written later by the chat stage.
+#[derive(Debug)]
+pub struct Draft {
+    pub quote: String,
+    pub source: String,
+    pub quote_letters: String,
+    pub answers: Vec<String>,
+}
+
+impl Draft {
+    pub fn from_text(text: &str) -> anyhow::Result<Self> {
+        let mut lines = text
+            .lines()
+            .map(|x| x.trim())
+            .filter(|x| !x.is_empty() && !x.starts_with('#'));
+        let quote = lines.next().ok_or_else(|| anyhow!("missing quote line"))?.to_string();
+        let source = lines.next().ok_or_else(|| anyhow!("missing source line"))?.to_string();
+        let quote_letters = catch_unwind(|| quote_to_cells(&quote))
+            .map_err(|_| anyhow!("quote contains a character quote_to_cells does not handle"))?;
+        Ok(Draft {
+            quote,
+            source,
+            quote_letters,
+            answers: lines.map(|x| x.to_string()).collect(),
+        })
+    }
+    /// Takes the quote grid and answers from the ipuz, ignoring its cell assignments.
+    pub fn from_ipuz(ipuz: &Ipuz) -> anyhow::Result<Self> {
+        ipuz.check_kind()?;
+        let (quote, source) = ipuz.quote_and_source();
+        Ok(Draft {
+            quote: quote.to_string(),
+            source: source.to_string(),
+            quote_letters: ipuz.quote_letters()?,
+            answers: ipuz.clues().iter().map(|x| x.answer.clone()).collect(),
+        })
+    }
+    pub fn to_puzzle(&self) -> Result<Puzzle, ImportError> {
+        let source_letters: String =
+            LetterString::from_str(&self.source).iter().map(|x| x.to_char()).collect();
+        if self.answers.len() != source_letters.len() {
+            return Err(ImportError::AnswerCount {
+                answers: self.answers.len(),
+                source_letters: source_letters.len(),
+            });
+        }
+        let answer_letters: Vec<String> = self
+            .answers
+            .iter()
+            .map(|x| LetterString::from_str(x).iter().map(|x| x.to_char()).collect())
+            .collect();
+        for ((answer, letters), expected) in
+            self.answers.iter().zip(&answer_letters).zip(source_letters.chars())
+        {
+            if !letters.starts_with(expected) {
+                return Err(ImportError::FirstLetter { answer: answer.clone(), expected });
+            }
+        }
+        let indices = assign_indices(&self.quote_letters, &answer_letters)?;
+        let mut puzzle = Puzzle::new(self.quote.clone(), self.source.clone());
+        puzzle.quote_letters = Some(self.quote_letters.clone());
+        puzzle.source_letters = Some(source_letters);
+        puzzle.clues = Some(
+            self.answers
+                .iter()
+                .zip(answer_letters)
+                .zip(indices)
+                .map(|((answer, answer_letters), indices)| Clue {
+                    clue: None,
+                    answer: answer.clone(),
+                    answer_letters,
+                    indices,
+                    source: None,
+                    breaks: Clue::breaks_in(answer),
+                })
+                .collect(),
+        );
+        Ok(puzzle)
+    }
+}
+
+/// Matches each answer letter to a quote cell with the same letter, taking cells in reading
+/// order. Fails unless the answers use every letter cell exactly once.
+pub fn assign_indices(
+    quote_letters: &str,
+    answer_letters: &[String],
+) -> Result<Vec<Vec<usize>>, ImportError> {
+    let mut positions = LetterMap::<Vec<usize>>::new();
+    for (i, c) in quote_letters.bytes().enumerate().rev() {
+        if let Ok(letter) = Letter::new(c) {
+            positions[letter].push(i);
+        }
+    }
+    let mut missing_from_quote = String::new();
+    let indices = answer_letters
+        .iter()
+        .map(|letters| {
+            letters
+                .bytes()
+                .flat_map(|x| Letter::new(x))
+                .filter_map(|letter| {
+                    let index = positions[letter].pop();
+                    if index.is_none() {
+                        missing_from_quote.push(letter.to_char());
+                    }
+                    index
+                })
+                .collect()
+        })
+        .collect();
+    let unused_quote: String = positions
+        .iter()
+        .flat_map(|(letter, rest)| rest.iter().map(move |_| letter.to_char()))
+        .collect();
+    if !unused_quote.is_empty() || !missing_from_quote.is_empty() {
+        let mut missing_from_quote: Vec<char> = missing_from_quote.chars().collect();
+        missing_from_quote.sort();
+        return Err(ImportError::Unbalanced {
+            unused_quote,
+            missing_from_quote: missing_from_quote.into_iter().collect(),
+        });
+    }
+    Ok(indices)
+}
+
+async fn read_draft(path: &Path) -> anyhow::Result<Draft> {
+    let input = read_path_to_string(path).await?;
+    if path.extension().is_some_and(|x| x == "ipuz") {
+        Draft::from_ipuz(&serde_json::from_str(&input)?)
+    } else {
+        Draft::from_text(&input)
+    }
+}
+
+/// Writes the imported puzzle's quote, letters and answers stages, so the chat stage can run
+/// next.
+pub async fn import_puzzle(args: &ImportArgs) -> anyhow::Result<()> {
+    let draft = read_draft(&args.file).await?;
+    let puzzle = draft.to_puzzle().map_err(|e| anyhow!("{:?}: {}", args.file, e))?;
+    let path = Stage::Quote.output_path(args.puzzle);
+    if !args.force && tokio::fs::try_exists(&path).await? {
+        return Err(anyhow!("{:?} already exists; pass --force to replace it", path));
+    }
+    let mut quote = Puzzle::new(puzzle.quote.clone(), puzzle.source.clone());
+    quote.write(args.puzzle, Stage::Quote).await?;
+    let mut letters = quote;
+    letters.quote_letters = puzzle.quote_letters.clone();
+    letters.source_letters = puzzle.source_letters.clone();
+    letters.write(args.puzzle, Stage::Letters).await?;
+    let mut answers = letters;
+    answers.clues = puzzle.clues;
+    answers.write(args.puzzle, Stage::Answers).await?;
+    Ok(())
+}
+
+#[test]
+fn test_import_text() {
+    let draft = Draft::from_text("# A tiny one\nNot toe, ant.\n\nTan\nTOE\nANT\nNOT\n").unwrap();
+    assert_eq!(draft.quote_letters, "NOT TOE ANT");
+    assert_eq!(draft.answers, vec!["TOE", "ANT", "NOT"]);
+    let puzzle = draft.to_puzzle().unwrap();
+    puzzle.validate().unwrap();
+    let indices: Vec<_> = puzzle.clues.unwrap().into_iter().map(|x| x.indices).collect();
+    assert_eq!(indices, vec![vec![2, 1, 6], vec![8, 0, 4], vec![9, 5, 10]]);
+
+    let mut draft = Draft::from_text("Not toe, ant.\nTan\nTOE\nANT\n").unwrap();
+    assert_eq!(
+        draft.to_puzzle().unwrap_err(),
+        ImportError::AnswerCount { answers: 2, source_letters: 3 }
+    );
+    draft.answers.push("OAT".to_string());
+    assert_eq!(
+        draft.to_puzzle().unwrap_err(),
+        ImportError::FirstLetter { answer: "OAT".to_string(), expected: 'N' }
+    );
+    draft.answers[2] = "NOTE".to_string();
+    assert_eq!(
+        draft.to_puzzle().unwrap_err(),
+        ImportError::Unbalanced { unused_quote: String::new(), missing_from_quote: "E".to_string() }
+    );
+    draft.answers[2] = "NO".to_string();
+    assert_eq!(
+        draft.to_puzzle().unwrap_err(),
+        ImportError::Unbalanced { unused_quote: "T".to_string(), missing_from_quote: String::new() }
+    );
+}
+
+#[test]
+fn test_import_ipuz() {
+    use crate::export::{clued_puzzles, ipuz, Acrostic};
+    for (pindex, puzzle) in clued_puzzles().into_iter().take(20) {
+        let ipuz = ipuz::from_acrostic(&Acrostic::new(&puzzle).unwrap(), "Test");
+        let imported = Draft::from_ipuz(&ipuz).unwrap().to_puzzle().unwrap();
+        imported.validate().unwrap();
+        assert_eq!(imported.quote_letters, puzzle.quote_letters, "puzzle {}", pindex);
+        assert_eq!(imported.source_letters, puzzle.source_letters, "puzzle {}", pindex);
+    }
+}
@@ -14,6 +14,9 @@ use std::sync::Arc;
 use std::time::Instant;
 use tokio::sync::Semaphore;
 
+/// Seed used by requests that do not set one.
+pub const DEFAULT_SEED: i32 = 123665;
+
 pub struct RpcBuilder<Req, Resp> {
     req: Req,
     training: Vec<(Req, Resp)>,
@@ -30,7 +33,7 @@ impl<Req: Serialize, Resp: JsonSchema + Serialize + for<'de> Deserialize<'de>>
             req,
             training: vec![],
             system,
-            seed: 123665,
+            seed: DEFAULT_SEED,
             model: MODEL_NAME,
         })
     }
@@ -30,10 +30,12 @@ use std::fs::File;
 use std::io::ErrorKind;
 use std::ops::{Deref, Index, IndexMut};
 use std::path::{Path, PathBuf};
-use std::sync::LazyLock;
+use std::sync::{LazyLock, OnceLock};
 use std::{env, fs, io, mem};
 
 use acrostic_core::letter::LetterSet;
+use anyhow::anyhow;
+use clap::Parser;
 use dict::build_dict;
 use memmap::MmapOptions;
 use ndarray::Array2;
@@ -49,31 +51,59 @@ use tokio::select;
 use tokio::signal::ctrl_c;
 use trie::build_trie;
 
+use crate::cli::{Cli, Command, GlobalTarget, PuzzleCommand};
 use crate::clues::{add_chat, ClueClient};
-use crate::quote::add_quote;
-use crate::search::add_answers;
+use crate::dict_manifest::DICT_MANIFEST_PATH;
+use crate::edit::edit_answers;
+use crate::export::export_puzzles;
+use crate::import::import_puzzle;
+use crate::pack::build_pack;
+use crate::pick::pick_answers;
+use crate::query::run_query;
+use crate::quote::{add_quote, QuoteFilter};
+use crate::pipeline::{run_pipeline, run_stage};
+use crate::render::render_puzzles;
+use crate::search::{add_answers, SearchOptions};
 // use crate::segment::add_letters;
 use crate::site::build_site;
+use crate::validate::validate_puzzles;
 // use crate::turtle::build_turtle;
 
+pub mod diagnose;
 pub mod dict;
+pub mod dict_manifest;
+pub mod edit;
+pub mod exact;
+pub mod export;
+pub mod import;
 pub mod model;
 pub mod puzzle;
 pub mod search;
 pub mod trie;
+pub mod trie_bundle;
 pub mod trie_table;
 // pub mod segment;
 mod add_letters;
 mod banned;
+pub mod cli;
 pub mod clues;
 pub mod llm;
 pub mod ontology;
+pub mod pack;
+pub mod pick;
+pub mod pipeline;
+pub mod provenance;
+pub mod query;
 pub mod quote;
+pub mod render;
+pub mod report;
+pub mod score;
 pub mod site;
 pub mod string;
 pub mod subseq;
 pub mod turtle;
 pub mod util;
+pub mod validate;
 mod lemma;
 pub mod conflict_set;
 mod cluedb;
@@ -86,14 +116,26 @@ use crate::turtle::db::build_ontolex_turtle;
 #[global_allocator]
 static GLOBAL: Jemalloc = Jemalloc;
 
+static DATA_DIR: OnceLock<PathBuf> = OnceLock::new();
+static PUZZLES_DIR: OnceLock<PathBuf> = OnceLock::new();
+
 pub static PACKAGE_PATH: LazyLock<PathBuf> = LazyLock::new(|| {
-    let path = env::var("CARGO_MANIFEST_DIR")
-        .map(|x| PathBuf::from(x).join(".."))
-        .unwrap_or(PathBuf::from(env::current_dir().unwrap()));
+    let path = DATA_DIR.get().cloned().unwrap_or_else(|| {
+        env::var("CARGO_MANIFEST_DIR")
+            .map(|x| PathBuf::from(x).join(".."))
+            .unwrap_or(PathBuf::from(env::current_dir().unwrap()))
+    });
     println!("PACKAGE_PATH = {:?}", path);
     path
 });
 
+pub static PUZZLES_PATH: LazyLock<PathBuf> = LazyLock::new(|| {
+    PUZZLES_DIR
+        .get()
+        .cloned()
+        .unwrap_or_else(|| PACKAGE_PATH.join("puzzles"))
+});
+
 pub async fn read_path(path: &Path) -> io::Result<Vec<u8>> {
     tokio::fs::read(path)
         .await
@@ -132,72 +174,51 @@ fn author_title_letters() -> LetterSet {
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-    Ok(run_with_interrupts(main_impl).await?)
+    let cli = Cli::parse();
+    if let Some(data_dir) = cli.data_dir.clone() {
+        DATA_DIR.set(data_dir).unwrap();
+    }
+    if let Some(puzzles_dir) = cli.puzzles_dir.clone() {
+        PUZZLES_DIR.set(puzzles_dir).unwrap();
+    }
+    if let Some(dict_manifest) = cli.dict_manifest.clone() {
+        DICT_MANIFEST_PATH.set(dict_manifest).unwrap();
+    }
+    Ok(run_with_interrupts(move |cleanup| main_impl(cli, cleanup)).await?)
 }
 
-async fn main_impl(cleanup: CleanupSender) -> anyhow::Result<()> {
-    let mut args = std::env::args().collect::<Vec<_>>().into_iter();
-    args.next().unwrap();
-    match args.next().as_deref() {
-        Some("global") => match args.next().as_deref() {
-            Some("quotes") => build_quotes().await?,
-            Some("dict") => build_dict().await?,
-            Some("trie") => build_trie().await?,
-            Some("site") => build_site().await?,
-            Some("turtle") => build_ontolex_turtle().await?,
-            x => panic!("Unknown global target {:?}", x),
+async fn main_impl(cli: Cli, cleanup: CleanupSender) -> anyhow::Result<()> {
+    match cli.command {
+        Command::Global { target } => match target {
+            GlobalTarget::Quotes => build_quotes().await?,
+            GlobalTarget::Dict => build_dict().await?,
+            GlobalTarget::Trie => build_trie().await?,
+            GlobalTarget::Site => build_site().await?,
+            GlobalTarget::Turtle => build_ontolex_turtle().await?,
         },
-        Some("puzzle") => {
-            let target = args.next().unwrap();
-            let mut puzzles = BTreeSet::new();
-            for puzzle in args {
-                if let Some((start, end)) = puzzle.split_once("-") {
-                    for x in start.parse().unwrap()..=end.parse().unwrap() {
-                        puzzles.insert(x);
-                    }
-                } else {
-                    puzzles.insert(puzzle.parse().unwrap());
-                }
-            }
-            let client = ClueClient::new(cleanup).await?;
-            let concurrency = match target.deref() {
-                "chat" => 1,
-                _ => 1,
+        Command::Pack(args) => build_pack(&args, cleanup).await?,
+        Command::Validate(args) => validate_puzzles(&args).await?,
+        Command::Export(args) => export_puzzles(&args).await?,
+        Command::Render(args) => render_puzzles(&args).await?,
+        Command::Import(args) => import_puzzle(&args).await?,
+        Command::Pick(args) => pick_answers(&args).await?,
+        Command::Query(args) => run_query(&args).await?,
+        Command::Edit(args) => edit_answers(&args).await?,
+        Command::Puzzle { stage: PuzzleCommand::All(args) } => {
+            run_pipeline(&args, cleanup).await?
+        }
+        Command::Puzzle { stage: command } => {
+            let quote_filter = match &command {
+                PuzzleCommand::Quote(args) => args.quote_filter.load().await?,
+                _ => QuoteFilter::default(),
+            };
+            let search = match &command {
+                PuzzleCommand::Answers(args) => args.search.options(),
+                _ => SearchOptions::default(),
             };
-            let errors = stream::iter(puzzles.into_iter())
-                .map(|puzzle| {
-                    let target = &target;
-                    let client = &client;
-                    async move {
-                        let e: anyhow::Result<()> = try {
-                            match target.deref() {
-                                "quote" => add_quote(puzzle).await?,
-                                "letters" => add_letters(puzzle).await?,
-                                "answers" => add_answers(puzzle).await?,
-                                "chat" => add_chat(puzzle, &client).await?,
-                                x => panic!("Unknown puzzle target {}", x),
-                            }
-                        };
-                        if let Err(e) = e {
-                            if e.downcast_ref::<io::Error>()
-                                .map_or(true, |x| x.kind() != io::ErrorKind::NotFound)
-                            {
-                                eprintln!("puzzle={} {}", puzzle, e);
-                                return Some(e);
-                            }
-                        } else {
-                            eprintln!("puzzle={} done", puzzle);
-                        }
-                        None
-                    }
-                })
-                .buffer_unordered(concurrency)
-                .collect::<Vec<Option<anyhow::Error>>>()
-                .await;
-            mem::drop(client);
-            eprintln!("{:?}", errors);
+            let (stage, args) = command.single_stage().unwrap();
+            run_stage(stage, args, quote_filter, search, cleanup).await?
         }
-        x => panic!("Unknown root command {:?}", x),
     }
     Ok(())
 }
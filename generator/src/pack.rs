@@ -0,0 +1,128 @@
+use std::io;
+use std::mem;
+use std::path::PathBuf;
+
+use anyhow::anyhow;
+use serde::{Deserialize, Serialize};
+use tokio::fs::create_dir_all;
+
+use crate::cli::PackArgs;
+use crate::pipeline::{print_timings, Pipeline, Stage};
+use crate::quote::QUOTES;
+use crate::report::Report;
+use crate::util::interrupt::CleanupSender;
+use crate::util::lazy_async::CloneError;
+use crate::{read_path_to_string, write_path, PACKAGE_PATH};
+
+/// A themed set of puzzles, shown as its own section of the site index.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct PackManifest {
+    pub title: String,
+    pub description: String,
+    pub topic: String,
+    pub puzzles: Vec<usize>,
+}
+
+pub fn packs_path() -> PathBuf {
+    PACKAGE_PATH.join("packs")
+}
+
+/// Reads every manifest under `packs/`, sorted by name.
+pub async fn read_packs() -> io::Result<Vec<(String, PackManifest)>> {
+    let mut packs = vec![];
+    let mut dir = match tokio::fs::read_dir(packs_path()).await {
+        Ok(dir) => dir,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(packs),
+        Err(e) => return Err(e),
+    };
+    while let Some(entry) = dir.next_entry().await? {
+        let path = entry.path();
+        let name = path.file_name().and_then(|x| x.to_str());
+        let Some(name) = name.and_then(|x| x.strip_suffix(".json")) else {
+            continue;
+        };
+        let manifest = serde_json::from_str(&read_path_to_string(&path).await?)?;
+        packs.push((name.to_string(), manifest));
+    }
+    packs.sort_by(|a, b| a.0.cmp(&b.0));
+    Ok(packs)
+}
+
+fn capitalize(x: &str) -> String {
+    let mut chars = x.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+/// Generates puzzles for the first quotes tagged with the pack's topic, moving on to further
+/// quotes whenever one fails, then writes the manifest.
+pub async fn build_pack(args: &PackArgs, cleanup: CleanupSender) -> anyhow::Result<()> {
+    let mut filter = args.quote_filter.load().await?;
+    filter.topics = vec![args.topic.clone()];
+    let quotes = QUOTES.get().await.clone_error_static()?;
+    let mut candidates = quotes
+        .iter()
+        .enumerate()
+        .filter(|(_, quote)| filter.check(quote).is_ok())
+        .map(|(pindex, _)| pindex);
+    let pipeline = Pipeline::new(
+        &args.concurrency,
+        Stage::Chat,
+        filter.clone(),
+        args.search.options(),
+        cleanup,
+    )
+    .await?;
+    let mut puzzles = vec![];
+    let mut runs = vec![];
+    while puzzles.len() < args.count {
+        let batch: Vec<usize> = candidates.by_ref().take(args.count - puzzles.len()).collect();
+        if batch.is_empty() {
+            break;
+        }
+        for run in pipeline.run_puzzles(batch).await {
+            if run.reached == Some(Stage::Chat) {
+                puzzles.push(run.pindex);
+            }
+            runs.push(run);
+        }
+    }
+    mem::drop(pipeline);
+    print_timings(&runs);
+    let mut report = Report::new();
+    for run in runs {
+        report.push_reached(run.pindex, run.reached, run.outcome);
+    }
+    report.print_summary();
+    if puzzles.len() < args.count {
+        return Err(anyhow!(
+            "only {} of {} puzzles could be generated for topic {:?}",
+            puzzles.len(),
+            args.count,
+            args.topic
+        ));
+    }
+    puzzles.sort();
+    let manifest = PackManifest {
+        title: args.title.clone().unwrap_or_else(|| capitalize(&args.topic)),
+        description: args.description.clone(),
+        topic: args.topic.clone(),
+        puzzles,
+    };
+    let name = args.name.as_ref().unwrap_or(&args.topic);
+    create_dir_all(packs_path()).await?;
+    write_path(
+        &packs_path().join(format!("{}.json", name)),
+        serde_json::to_string_pretty(&manifest)?.as_bytes(),
+    )
+    .await?;
+    Ok(())
+}
+
+#[test]
+fn test_capitalize() {
+    assert_eq!(capitalize("science"), "Science");
+    assert_eq!(capitalize(""), "");
+}
@@ -0,0 +1,108 @@
+//! Choosing between the answer sets the search kept in a puzzle's `alternatives`, so an editor
+//! can compare them before the chat stage spends LLM time cluing one.
+
+use anyhow::anyhow;
+use itertools::Itertools;
+use ordered_float::NotNan;
+
+use crate::cli::PickArgs;
+use crate::pipeline::Stage;
+use crate::puzzle::{Alternative, Clue, Puzzle, PuzzleFile};
+
+/// The alternative with the highest search score, the earliest on ties.
+pub fn best_alternative(alternatives: &[Alternative]) -> Option<usize> {
+    alternatives
+        .iter()
+        .enumerate()
+        .min_by_key(|(i, x)| (NotNan::new(-x.quality).unwrap(), *i))
+        .map(|(i, _)| i)
+}
+
+/// One line per alternative, marking the one the puzzle currently uses with `*`.
+pub fn describe(alternative: &Alternative, index: usize, current: &[Clue]) -> String {
+    let answers = |clues: &[Clue]| clues.iter().map(|x| x.answer.clone()).collect::<Vec<_>>();
+    let marker = if answers(&alternative.clues) == answers(current) {
+        '*'
+    } else {
+        ' '
+    };
+    let difficulty = match alternative.difficulty {
+        Some(x) => format!("{:.2}", x.score),
+        None => "-".to_string(),
+    };
+    format!(
+        "{}{:>3}  quality {:>7.2}  difficulty {:>4}  {}",
+        marker,
+        index,
+        alternative.quality,
+        difficulty,
+        alternative.clues.iter().map(|x| &x.answer).join(" ")
+    )
+}
+
+/// Prints the puzzle's alternatives, or rewrites its answers stage with the one chosen by
+/// `--index` or `--best`. Any clued stage becomes stale and is redone by the next pipeline run.
+pub async fn pick_answers(args: &PickArgs) -> anyhow::Result<()> {
+    let mut puzzle = Puzzle::read(args.puzzle, Stage::Answers).await?;
+    let alternatives = match puzzle.alternatives.clone() {
+        Some(x) if !x.is_empty() => x,
+        _ => {
+            return Err(anyhow!(
+                "puzzle {} has no alternatives; rerun its answers stage with --alternatives",
+                args.puzzle
+            ))
+        }
+    };
+    let index = match (args.index, args.best) {
+        (Some(index), _) => index,
+        (None, true) => best_alternative(&alternatives).unwrap(),
+        (None, false) => {
+            let current = puzzle.clues.clone().unwrap_or_default();
+            for (index, alternative) in alternatives.iter().enumerate() {
+                println!("{}", describe(alternative, index, &current));
+            }
+            return Ok(());
+        }
+    };
+    let alternative = alternatives.get(index).ok_or_else(|| {
+        anyhow!(
+            "puzzle {} has only {} alternatives",
+            args.puzzle,
+            alternatives.len()
+        )
+    })?;
+    puzzle.use_alternative(alternative);
+    puzzle.write(args.puzzle, Stage::Answers).await?;
+    eprintln!("puzzle={} using alternative {}", args.puzzle, index);
+    Ok(())
+}
+
+#[test]
+fn test_pick() {
+    let clue = |answer: &str| Clue {
+        clue: None,
+        answer: answer.to_string(),
+        answer_letters: answer.to_uppercase(),
+        indices: vec![],
+        source: None,
+        breaks: None,
+    };
+    let alternative = |quality: f64, answers: &[&str]| Alternative {
+        clues: answers.iter().map(|x| clue(x)).collect(),
+        quality,
+        search_seed: None,
+        difficulty: None,
+    };
+    let alternatives = [
+        alternative(2.0, &["tea", "ant"]),
+        alternative(3.0, &["toe", "ant"]),
+        alternative(3.0, &["ten", "ant"]),
+    ];
+    assert_eq!(best_alternative(&alternatives), Some(1));
+    assert_eq!(best_alternative(&[]), None);
+    assert_eq!(
+        describe(&alternatives[1], 1, &alternatives[1].clues),
+        "*  1  quality    3.00  difficulty    -  toe ant"
+    );
+    assert!(describe(&alternatives[0], 0, &alternatives[1].clues).starts_with("   0"));
+}
@@ -0,0 +1,346 @@
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
+use std::io;
+use std::mem;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use anyhow::anyhow;
+use clap::ValueEnum;
+use futures::stream::FuturesUnordered;
+use futures::{stream, StreamExt};
+use tokio::sync::Semaphore;
+
+use crate::add_letters::add_letters;
+use crate::cli::{ConcurrencyArgs, PipelineArgs, StageArgs};
+use crate::clues::{add_chat, ClueClient};
+use crate::puzzle::PuzzleState;
+use crate::quote::{add_quote, QuoteFilter};
+use crate::report::{Outcome, Report};
+use crate::search::{add_answers, AnswerConstraints, SearchOptions};
+use crate::util::interrupt::CleanupSender;
+use crate::{PACKAGE_PATH, PUZZLES_PATH};
+
+/// One step of puzzle generation. Each stage reads the previous stage's file and writes its own.
+#[derive(ValueEnum, Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum Stage {
+    Quote,
+    Letters,
+    Answers,
+    Chat,
+}
+
+/// The resource a stage is bottlenecked on; each has its own concurrency limit.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum StageKind {
+    Cpu,
+    Llm,
+}
+
+impl Stage {
+    pub const ALL: [Stage; 4] = [Stage::Quote, Stage::Letters, Stage::Answers, Stage::Chat];
+    pub fn output(self) -> &'static str {
+        match self {
+            Stage::Quote => "stage0.json",
+            Stage::Letters => "stage1.json",
+            Stage::Answers => "stage2.json",
+            Stage::Chat => "stage3.json",
+        }
+    }
+    pub fn input_path(self, pindex: usize) -> PathBuf {
+        match self {
+            Stage::Quote => PACKAGE_PATH.join("build/quotes.json"),
+            Stage::Letters => Stage::Quote.output_path(pindex),
+            Stage::Answers => Stage::Letters.output_path(pindex),
+            Stage::Chat => Stage::Answers.output_path(pindex),
+        }
+    }
+    pub fn output_path(self, pindex: usize) -> PathBuf {
+        PUZZLES_PATH.join(format!("{}", pindex)).join(self.output())
+    }
+    /// The state of a puzzle once this stage has run.
+    pub fn state(self) -> PuzzleState {
+        match self {
+            Stage::Quote => PuzzleState::Draft,
+            Stage::Letters => PuzzleState::Lettered,
+            Stage::Answers => PuzzleState::Answered,
+            Stage::Chat => PuzzleState::Clued,
+        }
+    }
+    pub fn kind(self) -> StageKind {
+        match self {
+            Stage::Quote | Stage::Letters | Stage::Answers => StageKind::Cpu,
+            Stage::Chat => StageKind::Llm,
+        }
+    }
+    /// Hand-edited files the stage reads when they exist, such as the answers stage's
+    /// `answers.toml`.
+    pub fn optional_inputs(self, pindex: usize) -> Vec<PathBuf> {
+        match self {
+            Stage::Answers => vec![AnswerConstraints::path(pindex)],
+            Stage::Quote | Stage::Letters | Stage::Chat => vec![],
+        }
+    }
+    /// Whether the output exists and was written after the input and any optional inputs.
+    pub async fn is_fresh(self, pindex: usize) -> io::Result<bool> {
+        let output = match tokio::fs::metadata(self.output_path(pindex)).await {
+            Ok(x) => x,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(false),
+            Err(e) => return Err(e),
+        };
+        let input = match tokio::fs::metadata(self.input_path(pindex)).await {
+            Ok(x) => x,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(false),
+            Err(e) => return Err(e),
+        };
+        if output.modified()? < input.modified()? {
+            return Ok(false);
+        }
+        for path in self.optional_inputs(pindex) {
+            match tokio::fs::metadata(path).await {
+                Ok(x) if x.modified()? > output.modified()? => return Ok(false),
+                Ok(_) => {}
+                Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(true)
+    }
+    pub async fn run(self, pindex: usize, context: &StageContext) -> anyhow::Result<()> {
+        match self {
+            Stage::Quote => add_quote(pindex, &context.quote_filter).await?,
+            Stage::Letters => add_letters(pindex).await?,
+            Stage::Answers => add_answers(pindex, &context.search).await?,
+            Stage::Chat => {
+                let client = context.client.as_ref().ok_or_else(|| anyhow!("no clue client"))?;
+                add_chat(pindex, client).await?
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Settings shared by every stage run in one invocation.
+pub struct StageContext {
+    /// Only present when an LLM stage will run.
+    pub client: Option<ClueClient>,
+    pub quote_filter: QuoteFilter,
+    pub search: SearchOptions,
+}
+
+impl Display for Stage {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Stage::Quote => "quote",
+            Stage::Letters => "letters",
+            Stage::Answers => "answers",
+            Stage::Chat => "chat",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// What happened to one puzzle during a pipeline run.
+pub struct PuzzleRun {
+    pub pindex: usize,
+    /// The furthest stage whose output is up to date.
+    pub reached: Option<Stage>,
+    pub outcome: Outcome,
+    timings: Vec<(Stage, Duration)>,
+    fresh: Vec<Stage>,
+}
+
+/// Runs puzzles through the stages, limiting how many CPU and LLM stages run at once.
+pub struct Pipeline {
+    cpu: Semaphore,
+    llm: Semaphore,
+    context: StageContext,
+    until: Stage,
+}
+
+impl Pipeline {
+    pub async fn new(
+        concurrency: &ConcurrencyArgs,
+        until: Stage,
+        quote_filter: QuoteFilter,
+        search: SearchOptions,
+        cleanup: CleanupSender,
+    ) -> anyhow::Result<Self> {
+        let client = if until >= Stage::Chat {
+            Some(ClueClient::new(cleanup).await?)
+        } else {
+            None
+        };
+        Ok(Pipeline {
+            cpu: Semaphore::new(concurrency.cpu_concurrency.max(1)),
+            llm: Semaphore::new(concurrency.llm_concurrency.max(1)),
+            context: StageContext {
+                client,
+                quote_filter,
+                search,
+            },
+            until,
+        })
+    }
+    fn semaphore(&self, stage: Stage) -> &Semaphore {
+        match stage.kind() {
+            StageKind::Cpu => &self.cpu,
+            StageKind::Llm => &self.llm,
+        }
+    }
+    async fn run_puzzle(&self, pindex: usize) -> PuzzleRun {
+        let mut run = PuzzleRun {
+            pindex,
+            reached: None,
+            outcome: Outcome::Done,
+            timings: vec![],
+            fresh: vec![],
+        };
+        for stage in Stage::ALL.into_iter().filter(|x| *x <= self.until) {
+            match stage.is_fresh(pindex).await {
+                Ok(true) => {
+                    run.reached = Some(stage);
+                    run.fresh.push(stage);
+                    continue;
+                }
+                Ok(false) => {}
+                Err(e) => {
+                    run.outcome = Outcome::from_result(Err(e.into()));
+                    return run;
+                }
+            }
+            let permit = self.semaphore(stage).acquire().await.unwrap();
+            let start = Instant::now();
+            let outcome = Outcome::catch(stage.run(pindex, &self.context)).await;
+            run.timings.push((stage, start.elapsed()));
+            mem::drop(permit);
+            match outcome {
+                Outcome::Done => {
+                    eprintln!("puzzle={} {} done", pindex, stage);
+                    run.reached = Some(stage);
+                }
+                outcome => {
+                    if let Outcome::Failed(e) = &outcome {
+                        eprintln!("puzzle={} {} {}", pindex, stage, e);
+                    }
+                    run.outcome = outcome;
+                    return run;
+                }
+            }
+        }
+        run
+    }
+    /// Moves every puzzle as far along the pipeline as it will go, skipping stages that are up to date.
+    pub async fn run_puzzles(&self, puzzles: impl IntoIterator<Item = usize>) -> Vec<PuzzleRun> {
+        puzzles
+            .into_iter()
+            .map(|pindex| self.run_puzzle(pindex))
+            .collect::<FuturesUnordered<_>>()
+            .collect()
+            .await
+    }
+}
+
+#[derive(Default)]
+struct StageTiming {
+    runs: usize,
+    fresh: usize,
+    total: Duration,
+    max: Duration,
+}
+
+pub fn print_timings(runs: &[PuzzleRun]) {
+    let mut timings = BTreeMap::<Stage, StageTiming>::new();
+    for run in runs {
+        for (stage, elapsed) in &run.timings {
+            let timing = timings.entry(*stage).or_default();
+            timing.runs += 1;
+            timing.total += *elapsed;
+            timing.max = timing.max.max(*elapsed);
+        }
+        for stage in &run.fresh {
+            timings.entry(*stage).or_default().fresh += 1;
+        }
+    }
+    eprintln!(
+        "{:>8}  {:>6}  {:>10}  {:>10}  {:>10}  {:>10}",
+        "stage", "runs", "up-to-date", "total", "mean", "max"
+    );
+    for (stage, timing) in timings {
+        let mean = timing.total.checked_div(timing.runs as u32).unwrap_or_default();
+        eprintln!(
+            "{:>8}  {:>6}  {:>10}  {:>10.1?}  {:>10.1?}  {:>10.1?}",
+            stage, timing.runs, timing.fresh, timing.total, mean, timing.max
+        );
+    }
+}
+
+/// Runs a single stage over a set of puzzles, regardless of whether its output is up to date.
+pub async fn run_stage(
+    stage: Stage,
+    args: &StageArgs,
+    quote_filter: QuoteFilter,
+    search: SearchOptions,
+    cleanup: CleanupSender,
+) -> anyhow::Result<()> {
+    let client = match stage.kind() {
+        StageKind::Llm => Some(ClueClient::new(cleanup).await?),
+        StageKind::Cpu => None,
+    };
+    let context = StageContext {
+        client,
+        quote_filter,
+        search,
+    };
+    let outcomes = stream::iter(args.puzzle_indices())
+        .map(|pindex| {
+            let context = &context;
+            async move {
+                let outcome = Outcome::catch(stage.run(pindex, context)).await;
+                match &outcome {
+                    Outcome::Done => eprintln!("puzzle={} done", pindex),
+                    Outcome::Failed(e) => eprintln!("puzzle={} {}", pindex, e),
+                    Outcome::Skipped(_) | Outcome::Rejected(_) => {}
+                }
+                (pindex, outcome)
+            }
+        })
+        .buffer_unordered(args.concurrency.max(1))
+        .collect::<Vec<_>>()
+        .await;
+    mem::drop(context);
+    let mut report = Report::new();
+    for (pindex, outcome) in outcomes {
+        report.push(pindex, outcome);
+    }
+    report.print_summary();
+    if report.failures() > 0 {
+        return Err(anyhow!("{} puzzles failed", report.failures()));
+    }
+    Ok(())
+}
+
+/// Runs the pipeline over the puzzles named on the command line.
+pub async fn run_pipeline(args: &PipelineArgs, cleanup: CleanupSender) -> anyhow::Result<()> {
+    let quote_filter = args.quote_filter.load().await?;
+    let pipeline = Pipeline::new(
+        &args.concurrency,
+        args.until,
+        quote_filter,
+        args.search.options(),
+        cleanup,
+    )
+    .await?;
+    let runs = pipeline.run_puzzles(args.puzzle_indices()).await;
+    mem::drop(pipeline);
+    print_timings(&runs);
+    let mut report = Report::new();
+    for run in runs {
+        report.push_reached(run.pindex, run.reached, run.outcome);
+    }
+    report.print_summary();
+    if report.failures() > 0 {
+        return Err(anyhow!("{} puzzles failed", report.failures()));
+    }
+    Ok(())
+}
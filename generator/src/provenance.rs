@@ -0,0 +1,56 @@
+use std::fs::File;
+use std::io;
+use std::path::Path;
+use std::sync::LazyLock;
+
+use safe_once_async::async_lazy::AsyncLazy;
+use safe_once_async::detached::{spawn_transparent, JoinTransparent};
+use safe_once_async::sync::AsyncLazyLock;
+use sha2::{Digest, Sha256};
+
+use crate::dict_manifest::DICT_SELECTION;
+use crate::trie_bundle::BUNDLE_FILE;
+use crate::util::lazy_async::CloneError;
+use crate::PACKAGE_PATH;
+
+/// The commit the generator was built from, with `-dirty` if the tree had uncommitted changes.
+pub const REVISION: &str = match option_env!("GENERATOR_REVISION") {
+    Some(x) => x,
+    None => "unknown",
+};
+
+/// SHA-256 of the artifacts under `build/` that determine search results.
+pub struct ArtifactHashes {
+    pub dict: String,
+    pub trie: String,
+}
+
+pub static ARTIFACT_HASHES: LazyLock<AsyncLazyLock<JoinTransparent<anyhow::Result<ArtifactHashes>>>> =
+    LazyLock::new(|| {
+        AsyncLazy::new(spawn_transparent(async {
+            let dir = DICT_SELECTION.get().await.clone_error_static()?.dir();
+            Ok(tokio::task::spawn_blocking(move || ArtifactHashes::new(&dir)).await??)
+        }))
+    });
+
+fn hash_file(hasher: &mut Sha256, path: &Path) -> io::Result<()> {
+    let mut file = File::open(path)
+        .map_err(|e| io::Error::new(e.kind(), format!("Cannot read {:?}: {}", path, e)))?;
+    io::copy(&mut file, hasher)?;
+    Ok(())
+}
+
+impl ArtifactHashes {
+    /// Hashes the dictionary and the trie table in `trie_dir`.
+    fn new(trie_dir: &Path) -> io::Result<Self> {
+        let mut dict = Sha256::new();
+        hash_file(&mut dict, &PACKAGE_PATH.join("build/dict.dat"))?;
+        let mut trie = Sha256::new();
+        hash_file(&mut trie, &trie_dir.join(BUNDLE_FILE))?;
+        Ok(ArtifactHashes {
+            dict: hex::encode(dict.finalize()),
+            trie: hex::encode(trie.finalize()),
+        })
+    }
+}
+
@@ -1,59 +1,38 @@
-use std::{fs, io};
+use std::future::Future;
+use std::io;
 
-use serde::{Deserialize, Serialize};
-use crate::{PACKAGE_PATH, read_path_to_string, write_path};
+pub use acrostic_core::puzzle::{
+    Alternative, Clue, ClueSource, Difficulty, DifficultyLevel, Provenance, Puzzle, PuzzleState,
+};
 
-// #[derive(Serialize, Deserialize, Debug)]
-// pub struct GivenCell {
-//     pub contents: String,
-//     pub visible: bool,
-// }
-//
-// #[derive(Serialize, Deserialize, Debug)]
-// pub struct EmptyCell {
-//     pub letter: Letter,
-//     pub content: Option<String>,
-//     pub clue_index: Option<usize>,
-//     pub clue_offset: Option<usize>,
-// }
-//
-// #[derive(Serialize, Deserialize, Debug)]
-// pub enum Cell {
-//     Given(GivenCell),
-//     Empty(EmptyCell),
-// }
+use crate::pipeline::Stage;
+use crate::provenance::REVISION;
+use crate::{read_path_to_string, write_path};
 
-#[derive(Serialize, Deserialize, Debug)]
-pub struct Clue {
-    pub clue: Option<String>,
-    pub answer: String,
-    pub answer_letters: String,
-    pub indices: Vec<usize>,
+/// Reading and writing puzzles in the per-stage files under `PUZZLES_PATH`.
+pub trait PuzzleFile: Sized {
+    fn read(index: usize, stage: Stage) -> impl Future<Output = io::Result<Self>> + Send;
+    /// Marks the puzzle as having completed `stage`, validates it, and writes the stage's output.
+    fn write(&mut self, index: usize, stage: Stage) -> impl Future<Output = io::Result<()>> + Send;
 }
 
-#[derive(Serialize, Deserialize, Debug)]
-pub struct Puzzle {
-    pub quote: String,
-    pub quote_letters: Option<String>,
-    pub source: String,
-    pub source_letters: Option<String>,
-    pub clues: Option<Vec<Clue>>,
-    pub chat: Option<String>,
-}
-
-impl Puzzle {
-    pub async fn read(index: usize, stage: &str) -> io::Result<Puzzle> {
-        let input = read_path_to_string(
-            &PACKAGE_PATH.join("puzzles").join(&format!("{}", index)).join(stage)).await?;
-        Ok(serde_json::from_str(&input)?)
+impl PuzzleFile for Puzzle {
+    async fn read(index: usize, stage: Stage) -> io::Result<Puzzle> {
+        let path = stage.output_path(index);
+        let input = read_path_to_string(&path).await?;
+        Puzzle::from_json(&input)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("{:?}: {}", path, e)))
     }
-    pub async fn write(&self, index: usize, stage: &str) -> io::Result<()> {
-        let dir = PACKAGE_PATH.join("puzzles").join(&format!("{}", index));
-        tokio::fs::create_dir_all(&dir).await?;
-        write_path(
-            &dir.join(stage),
-            &serde_json::to_string_pretty(self).unwrap().as_bytes(),
-        ).await?;
+    async fn write(&mut self, index: usize, stage: Stage) -> io::Result<()> {
+        self.state = stage.state();
+        self.provenance_mut()
+            .revisions
+            .insert(stage.to_string(), REVISION.to_string());
+        let path = stage.output_path(index);
+        self.validate()
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("{:?}: {}", path, e)))?;
+        tokio::fs::create_dir_all(path.parent().unwrap()).await?;
+        write_path(&path, &serde_json::to_string_pretty(self).unwrap().as_bytes()).await?;
         Ok(())
     }
 }
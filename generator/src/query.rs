@@ -0,0 +1,333 @@
+//! Searches over the dictionary for editors fixing up answer sets by hand: anagrams,
+//! words that fit within a set of letters, and crossword-style patterns.
+
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::ops::RangeInclusive;
+use std::str::FromStr;
+
+use acrostic_core::letter::{Letter, LetterSet};
+use anyhow::anyhow;
+
+use crate::cli::{QueryArgs, QueryCommand};
+use crate::dict::FlatWord;
+use crate::pipeline::Stage;
+use crate::puzzle::{Puzzle, PuzzleFile};
+use crate::trie_table::{FlatTrieTable, FLAT_TRIE_TABLE};
+use crate::util::lazy_async::CloneError;
+
+/// A crossword-style pattern such as `?A??E`, with `?` or `.` for an unknown letter.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Pattern(pub Vec<Option<Letter>>);
+
+impl FromStr for Pattern {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let pattern = s
+            .bytes()
+            .map(|c| match c {
+                b'?' | b'.' => Ok(None),
+                c => Letter::new(c)
+                    .map(Some)
+                    .map_err(|_| anyhow!("{:?} is not a letter, '?' or '.'", c as char)),
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        if pattern.is_empty() {
+            return Err(anyhow!("empty pattern"));
+        }
+        Ok(Pattern(pattern))
+    }
+}
+
+impl Display for Pattern {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for x in &self.0 {
+            match x {
+                Some(l) => write!(f, "{}", l)?,
+                None => write!(f, "?")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Pattern {
+    pub fn matches(&self, word: &FlatWord) -> bool {
+        word.letter_vec.len() == self.0.len()
+            && self
+                .0
+                .iter()
+                .zip(word.letter_vec.iter())
+                .all(|(p, l)| p.is_none_or(|p| p == *l))
+    }
+}
+
+/// The words of a trie table, found by the letters they use. Words shorter than the table's
+/// `min_letters` are not in its tries, so lookups reaching below that length scan the whole
+/// dictionary for them instead.
+pub struct WordQuery<'a> {
+    table: &'a FlatTrieTable,
+    words: HashMap<(Letter, LetterSet), Vec<&'static FlatWord>>,
+}
+
+impl<'a> WordQuery<'a> {
+    pub fn new(table: &'a FlatTrieTable) -> Self {
+        let mut words = HashMap::<_, Vec<_>>::new();
+        for word in table.words() {
+            if let Some(first) = word.letter_vec.first() {
+                words.entry((*first, word.letters)).or_default().push(word);
+            }
+        }
+        WordQuery { table, words }
+    }
+    /// The words behind trie values found under `first`, most frequent first.
+    fn expand(&self, first: Letter, found: &[LetterSet], result: &mut Vec<&'static FlatWord>) {
+        for letters in found {
+            if let Some(words) = self.words.get(&(first, *letters)) {
+                result.extend(words);
+            }
+        }
+    }
+    fn min_letters(&self) -> usize {
+        self.table.selection.manifest.min_letters
+    }
+    /// Dictionary words too short for the tries that pass `keep`, leaving out words the
+    /// manifest bans.
+    fn short_words(&self, keep: impl Fn(&FlatWord) -> bool) -> Vec<&'static FlatWord> {
+        let min_letters = self.min_letters();
+        let selection = self.table.selection;
+        self.table
+            .dict
+            .iter()
+            .filter(|x| x.letters.count() < min_letters && !selection.is_banned(&x.word) && keep(x))
+            .collect()
+    }
+    fn sorted(mut words: Vec<&'static FlatWord>) -> Vec<&'static FlatWord> {
+        words.sort_by_key(|x| (Reverse(x.frequency), x.word.as_str()));
+        words.dedup_by_key(|x| x.word.as_str());
+        words
+    }
+    /// Words using exactly `letters`.
+    pub fn anagrams(&self, letters: LetterSet) -> Vec<&'static FlatWord> {
+        if letters.count() < self.min_letters() {
+            return Self::sorted(self.short_words(|x| x.letters == letters));
+        }
+        let mut result = vec![];
+        for first in Letter::all() {
+            if letters[first] == 0 {
+                continue;
+            }
+            if let Some(found) = self.table.unary[first].search_exact(letters) {
+                self.expand(first, &[*found], &mut result);
+            }
+        }
+        Self::sorted(result)
+    }
+    /// Words starting with `first` that use only letters in `letters`. Only the tries are
+    /// searched, so these are all words the answer search may use.
+    pub fn starting_with(&self, first: Letter, letters: LetterSet) -> Vec<&'static FlatWord> {
+        let mut found = vec![];
+        self.table.unary[first].search_all_subsets(letters, &mut found);
+        let mut result = vec![];
+        self.expand(first, &found, &mut result);
+        Self::sorted(result)
+    }
+    /// Words that use only letters in `letters`, with a letter count in `lengths`.
+    pub fn within(
+        &self,
+        letters: LetterSet,
+        lengths: RangeInclusive<usize>,
+    ) -> Vec<&'static FlatWord> {
+        let mut result = vec![];
+        for first in Letter::all() {
+            if letters[first] == 0 {
+                continue;
+            }
+            let mut found = vec![];
+            self.table.unary[first].search_all_subsets(letters, &mut found);
+            found.retain(|x| lengths.contains(&x.count()));
+            self.expand(first, &found, &mut result);
+        }
+        if *lengths.start() < self.min_letters() {
+            result.extend(self.short_words(|x| {
+                x.letters.is_subset(letters) && lengths.contains(&x.letters.count())
+            }));
+        }
+        Self::sorted(result)
+    }
+    /// Words matching `pattern`, and when `letters` is given, using only those letters.
+    pub fn matching(
+        &self,
+        pattern: &Pattern,
+        letters: Option<LetterSet>,
+    ) -> Vec<&'static FlatWord> {
+        let len = pattern.0.len();
+        if len < self.min_letters() {
+            return Self::sorted(self.short_words(|x| {
+                pattern.matches(x) && letters.is_none_or(|letters| x.letters.is_subset(letters))
+            }));
+        }
+        let candidates = match (letters, pattern.0[0]) {
+            (Some(letters), Some(first)) => self.starting_with(first, letters),
+            (Some(letters), None) => self.within(letters, len..=len),
+            (None, _) => self.table.words(),
+        };
+        Self::sorted(
+            candidates
+                .into_iter()
+                .filter(|x| pattern.matches(x))
+                .collect(),
+        )
+    }
+}
+
+/// The letters left for answer `answer` of a puzzle: its quote's letters less those of every
+/// other answer. Also returns the letter the answer must start with.
+pub async fn remaining_letters(
+    puzzle: usize,
+    answer: usize,
+) -> anyhow::Result<(Letter, LetterSet)> {
+    let puzzle_file = Puzzle::read(puzzle, Stage::Answers).await?;
+    let clues = puzzle_file
+        .clues
+        .as_ref()
+        .ok_or_else(|| anyhow!("puzzle {} has no answers", puzzle))?;
+    let source = puzzle_file.source_letters.as_deref().unwrap_or_default();
+    let first = source
+        .as_bytes()
+        .get(answer)
+        .and_then(|x| Letter::new(*x).ok())
+        .ok_or_else(|| anyhow!("puzzle {} has only {} answers", puzzle, clues.len()))?;
+    let quote = LetterSet::from_str(puzzle_file.quote_letters.as_deref().unwrap_or_default());
+    let mut others = LetterSet::new();
+    for (i, clue) in clues.iter().enumerate() {
+        if i != answer {
+            others = others + LetterSet::from_str(&clue.answer_letters);
+        }
+    }
+    if !others.is_subset(quote) {
+        return Err(anyhow!(
+            "puzzle {}'s answers use letters its quote lacks",
+            puzzle
+        ));
+    }
+    Ok((first, quote - others))
+}
+
+pub async fn run_query(args: &QueryArgs) -> anyhow::Result<()> {
+    let table = FLAT_TRIE_TABLE.get().await.clone_error_static()?;
+    let query = WordQuery::new(table);
+    let words = match &args.query {
+        QueryCommand::Anagram { letters } => query.anagrams(LetterSet::from_str(letters)),
+        QueryCommand::Within {
+            letters,
+            puzzle,
+            answer,
+            first,
+            min_len,
+            max_len,
+        } => {
+            let first = match first {
+                Some(c) => {
+                    Some(Letter::new(*c as u8).map_err(|_| anyhow!("{:?} is not a letter", c))?)
+                }
+                None => None,
+            };
+            let (first, letters) = match (letters, puzzle, answer) {
+                (Some(letters), _, _) => (first, LetterSet::from_str(letters)),
+                (None, Some(puzzle), Some(answer)) => {
+                    let (source, letters) = remaining_letters(*puzzle, *answer).await?;
+                    (first.or(Some(source)), letters)
+                }
+                _ => return Err(anyhow!("give either letters or --puzzle with --answer")),
+            };
+            let lengths = min_len.unwrap_or(1)..=max_len.unwrap_or(usize::MAX);
+            let words = query.within(letters, lengths);
+            match first {
+                Some(first) => words
+                    .into_iter()
+                    .filter(|x| x.letter_vec.first() == Some(&first))
+                    .collect(),
+                None => words,
+            }
+        }
+        QueryCommand::Pattern { pattern, within } => {
+            query.matching(pattern, within.as_deref().map(LetterSet::from_str))
+        }
+    };
+    for word in words.iter().take(args.limit) {
+        println!("{}\t{}", word.word, word.frequency);
+    }
+    if words.len() > args.limit {
+        println!("... and {} more", words.len() - args.limit);
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_word_query() -> anyhow::Result<()> {
+    use crate::dict_manifest::DictManifest;
+    use crate::trie_table::{fixture, fixture_with};
+    let table = fixture(&["tea", "eat", "ate", "toe", "ant", "not", "tone", "note"]).await?;
+    let query = WordQuery::new(&table);
+    let words = |x: Vec<&FlatWord>| x.iter().map(|x| x.word.to_string()).collect::<Vec<_>>();
+    let t = Letter::new(b't').unwrap();
+
+    assert_eq!(
+        words(query.anagrams(LetterSet::from_str("aet"))),
+        vec!["ate", "eat", "tea"]
+    );
+    assert_eq!(
+        words(query.anagrams(LetterSet::from_str("aetx"))),
+        Vec::<String>::new()
+    );
+    assert_eq!(
+        words(query.starting_with(t, LetterSet::from_str("teonx"))),
+        vec!["toe", "tone"]
+    );
+    assert_eq!(
+        words(query.within(LetterSet::from_str("notex"), 4..=4)),
+        vec!["note", "tone"]
+    );
+    assert_eq!(
+        words(query.within(LetterSet::from_str("note"), 1..=3)),
+        vec!["not", "toe"]
+    );
+    let pattern: Pattern = "?o?e".parse()?;
+    assert_eq!(pattern.to_string(), "?O?E");
+    assert_eq!(words(query.matching(&pattern, None)), vec!["note", "tone"]);
+    let pattern: Pattern = "t..".parse()?;
+    assert_eq!(words(query.matching(&pattern, None)), vec!["tea", "toe"]);
+    assert_eq!(
+        words(query.matching(&pattern, Some(LetterSet::from_str("toex")))),
+        vec!["toe"]
+    );
+    assert!("t?x!".parse::<Pattern>().is_err());
+    assert!("".parse::<Pattern>().is_err());
+
+    // Words shorter than the tries' minimum come from the whole dictionary, less banned ones.
+    let manifest = DictManifest {
+        min_letters: 4,
+        banned: vec!["toe".to_string()],
+        ..DictManifest::default()
+    };
+    let dict = ["tea", "eat", "toe", "at", "tone", "note", "atone"];
+    let table = fixture_with(&dict, manifest).await?;
+    let query = WordQuery::new(&table);
+    assert_eq!(words(query.anagrams(LetterSet::from_str("aet"))), vec!["eat", "tea"]);
+    assert_eq!(
+        words(query.within(LetterSet::from_str("notea"), 2..=4)),
+        vec!["at", "eat", "note", "tea", "tone"]
+    );
+    assert_eq!(
+        words(query.within(LetterSet::from_str("notea"), 4..=5)),
+        vec!["atone", "note", "tone"]
+    );
+    assert_eq!(words(query.matching(&"?E?".parse()?, None)), vec!["tea"]);
+    assert_eq!(
+        words(query.matching(&"?O?E".parse()?, Some(LetterSet::from_str("tone")))),
+        vec!["note", "tone"]
+    );
+    Ok(())
+}
@@ -1,7 +1,11 @@
+use std::error::Error;
+use std::fmt::{Display, Formatter};
 use std::io::{Cursor, ErrorKind, Read};
 use std::sync::LazyLock;
 use std::time::Instant;
-use std::{fs, io, slice};
+use std::{fmt, fs, io, slice};
+
+use acrostic_core::letter::LetterSet;
 
 use itertools::{peek_nth, PeekNth};
 use rand::seq::SliceRandom;
@@ -12,7 +16,9 @@ use safe_once_async::sync::AsyncLazyLock;
 use serde::Deserialize;
 use serde::Serialize;
 
-use crate::puzzle::Puzzle;
+use crate::pipeline::Stage;
+use crate::puzzle::{Puzzle, PuzzleFile};
+use crate::string::LetterString;
 use crate::util::lazy_async::CloneError;
 use crate::{read_path, read_path_to_string, write_path, PACKAGE_PATH};
 // use crate::util::lazy_async::LazyAsync;
@@ -151,33 +157,134 @@ pub async fn build_quotes() -> io::Result<()> {
     Ok(())
 }
 
-pub async fn add_quote(pindex: usize) -> io::Result<()> {
-    let mut quotes = QUOTES.get().await.clone_error_static()?;
-    let quote = &quotes[pindex];
-    if !(quote.source.len() >= 23
-        && quote.source.len() <= 26
-        && quote.quote.len() > 180
-        && quote.quote.len() < 200)
-    {
-        return Err(io::Error::new(
-            ErrorKind::InvalidInput,
-            format!("bad quote {} {}", quote.source.len(), quote.quote.len()),
-        ));
+/// Criteria a quote must meet to become a puzzle. Lengths are counted in letters, ignoring
+/// spaces and punctuation, so they translate directly into grid size and answer count.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct QuoteFilter {
+    pub min_quote_letters: usize,
+    pub max_quote_letters: usize,
+    pub min_source_letters: usize,
+    pub max_source_letters: usize,
+    /// If non-empty, the quote must be tagged with at least one of these topics.
+    pub topics: Vec<String>,
+    /// Authors whose quotes are never used, matched against the part of the source before the first comma.
+    pub banned_authors: Vec<String>,
+    /// Require every source letter to appear in the quote at least as often as in the source.
+    pub check_letters: bool,
+}
+
+/// The default letter windows are the extremes measured over the 1,108 quotes in `puzzles/`,
+/// which the old byte limits (181–199 quote bytes, 23–26 source bytes) selected, so every one
+/// of them still passes. The letter check turns away only quotes the answer search could never
+/// solve; none of the 481 puzzles with answers fails it.
+impl Default for QuoteFilter {
+    fn default() -> Self {
+        QuoteFilter {
+            min_quote_letters: 118,
+            max_quote_letters: 168,
+            min_source_letters: 10,
+            max_source_letters: 25,
+            topics: vec![],
+            banned_authors: vec![],
+            check_letters: true,
+        }
     }
+}
 
-    // let (index, selected) = quotes.into_iter().enumerate()
-    //     .filter(|(index, quote)| quote.source.len() > 22
-    //         && quote.source.len() <= 24
-    //         && quote.quote.len() > 180
-    //         && quote.quote.len() < 200).nth(pindex).unwrap();
-    let puzzle = Puzzle {
-        quote: quote.quote.clone(),
-        quote_letters: None,
-        source: quote.source.clone(),
-        source_letters: None,
-        clues: None,
-        chat: None,
-    };
-    puzzle.write(pindex, "stage0.json").await?;
+/// A quote that exists but does not meet the [QuoteFilter].
+#[derive(Debug)]
+pub struct QuoteRejected(pub String);
+
+impl Display for QuoteRejected {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "rejected: {}", self.0)
+    }
+}
+
+impl Error for QuoteRejected {}
+
+impl QuoteFilter {
+    pub fn from_toml(contents: &str) -> anyhow::Result<Self> {
+        Ok(toml::from_str(contents)?)
+    }
+    pub fn check(&self, quote: &Quote) -> Result<(), QuoteRejected> {
+        let quote_letters = LetterString::from_str(&quote.quote);
+        let source_letters = LetterString::from_str(&quote.source);
+        if !(self.min_quote_letters..=self.max_quote_letters).contains(&quote_letters.len()) {
+            return Err(QuoteRejected(format!("quote has {} letters", quote_letters.len())));
+        }
+        if !(self.min_source_letters..=self.max_source_letters).contains(&source_letters.len()) {
+            return Err(QuoteRejected(format!("source has {} letters", source_letters.len())));
+        }
+        if !self.topics.is_empty()
+            && !quote
+                .topics
+                .iter()
+                .any(|x| self.topics.iter().any(|y| x.eq_ignore_ascii_case(y)))
+        {
+            return Err(QuoteRejected(format!("topics {:?}", quote.topics)));
+        }
+        let author = quote.source.split(',').next().unwrap_or_default().trim();
+        if self
+            .banned_authors
+            .iter()
+            .any(|x| x.trim().eq_ignore_ascii_case(author))
+        {
+            return Err(QuoteRejected(format!("banned author {:?}", author)));
+        }
+        if self.check_letters {
+            let quote_set: LetterSet = quote_letters.iter().copied().collect();
+            let source_set: LetterSet = source_letters.iter().copied().collect();
+            if !source_set.is_subset(quote_set) {
+                let missing: String = source_set
+                    .iter()
+                    .filter(|(l, c)| *c > quote_set[*l] as usize)
+                    .map(|(l, _)| l.to_char())
+                    .collect();
+                return Err(QuoteRejected(format!("quote is short of {}", missing)));
+            }
+        }
+        Ok(())
+    }
+}
+
+pub async fn add_quote(pindex: usize, filter: &QuoteFilter) -> anyhow::Result<()> {
+    let quotes = QUOTES.get().await.clone_error_static()?;
+    let quote = quotes.get(pindex).ok_or_else(|| {
+        io::Error::new(ErrorKind::NotFound, format!("No quote {}", pindex))
+    })?;
+    filter.check(quote)?;
+    let mut puzzle = Puzzle::new(quote.quote.clone(), quote.source.clone());
+    puzzle.write(pindex, Stage::Quote).await?;
     Ok(())
 }
+
+#[test]
+fn test_quote_filter() {
+    let quote = |quote: &str, source: &str, topics: &[&str]| Quote {
+        quote: quote.to_string(),
+        source: source.to_string(),
+        topics: topics.iter().map(|x| x.to_string()).collect(),
+    };
+    let filter = QuoteFilter {
+        min_quote_letters: 10,
+        max_quote_letters: 30,
+        min_source_letters: 3,
+        max_source_letters: 10,
+        topics: vec!["Life".to_string()],
+        banned_authors: vec!["Ann Other".to_string()],
+        check_letters: true,
+    };
+    let good = quote("Anything goes, as they say in Ohio.", "Tony Hay", &["life"]);
+    filter.check(&good).unwrap();
+    let short = quote("Anything.", "Tony Hay", &["life"]);
+    assert!(filter.check(&short).is_err());
+    let off_topic = quote("Anything goes, as they say in Ohio.", "Tony Hay", &["love"]);
+    assert!(filter.check(&off_topic).is_err());
+    let banned = quote("Anything goes, as they say in Ohio.", "ann other, Essays", &["life"]);
+    assert!(filter.check(&banned).is_err());
+    // Only one Z in the quote.
+    let infeasible = quote("Anything goes, as they say in Ohio zoo.", "Zizi", &["life"]);
+    assert!(filter.check(&infeasible).is_err());
+}
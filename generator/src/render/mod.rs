@@ -0,0 +1,353 @@
+use std::path::PathBuf;
+
+use acrostic_core::puzzle::Puzzle;
+use anyhow::anyhow;
+use tokio::fs::create_dir_all;
+
+use crate::cli::RenderArgs;
+use crate::export::{clue_label, Acrostic, QUOTE_WIDTH};
+use crate::pipeline::Stage;
+use crate::puzzle::PuzzleFile;
+use crate::report::{Outcome, Report};
+use crate::{write_path, PACKAGE_PATH};
+
+pub mod pdf;
+pub mod svg;
+
+/// Dimensions are in points, with the origin at the top left of the page.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Shape {
+    Rect {
+        x: f64,
+        y: f64,
+        width: f64,
+        height: f64,
+        filled: bool,
+    },
+    Line {
+        x1: f64,
+        y1: f64,
+        x2: f64,
+        y2: f64,
+    },
+    /// Set in Courier, so the width is `0.6 * size` per character.
+    Text {
+        x: f64,
+        y: f64,
+        size: f64,
+        anchor: Anchor,
+        text: String,
+    },
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Anchor {
+    Start,
+    Middle,
+    End,
+}
+
+pub struct Page {
+    pub width: f64,
+    pub height: f64,
+    pub shapes: Vec<Shape>,
+}
+
+const PAGE_WIDTH: f64 = 612.0;
+const PAGE_HEIGHT: f64 = 792.0;
+const MARGIN: f64 = 36.0;
+const CELL: f64 = (PAGE_WIDTH - 2.0 * MARGIN) / QUOTE_WIDTH as f64;
+const GUTTER: f64 = 18.0;
+const COLUMN_WIDTH: f64 = (PAGE_WIDTH - 2.0 * MARGIN - GUTTER) / 2.0;
+const BLANK: f64 = 16.0;
+/// The height of one row of answer blanks with their cell numbers.
+const BLANK_ROW: f64 = 28.0;
+const CLUE_GAP: f64 = 6.0;
+const CLUE_SIZE: f64 = 9.0;
+const CLUE_LINE: f64 = 11.0;
+const TITLE_HEIGHT: f64 = 30.0;
+
+/// Greedily breaks `text` into lines of at most `width` characters.
+fn wrap(text: &str, width: usize) -> Vec<String> {
+    let mut lines = vec![];
+    let mut line = String::new();
+    for word in text.split_whitespace() {
+        if !line.is_empty() && line.len() + 1 + word.len() > width {
+            lines.push(std::mem::take(&mut line));
+        }
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        line.push_str(word);
+    }
+    if !line.is_empty() {
+        lines.push(line);
+    }
+    lines
+}
+
+fn text(x: f64, y: f64, size: f64, anchor: Anchor, text: impl Into<String>) -> Shape {
+    Shape::Text {
+        x,
+        y,
+        size,
+        anchor,
+        text: text.into(),
+    }
+}
+
+fn chars_per_line(width: f64, size: f64) -> usize {
+    (width / (0.6 * size)) as usize
+}
+
+/// Lays blocks of shapes down two columns, starting a new page when both are full.
+struct Flow {
+    title: String,
+    pages: Vec<Vec<Shape>>,
+    column: usize,
+    /// Where the columns start on the current page.
+    top: f64,
+    y: f64,
+    /// The lowest point used on the current page.
+    bottom: f64,
+}
+
+impl Flow {
+    fn new(title: &str) -> Self {
+        let top = MARGIN + TITLE_HEIGHT;
+        Flow {
+            title: title.to_string(),
+            pages: vec![vec![text(MARGIN, MARGIN + 14.0, 16.0, Anchor::Start, title)]],
+            column: 0,
+            top,
+            y: top,
+            bottom: top,
+        }
+    }
+    fn new_page(&mut self) {
+        let title = format!("{} (continued)", self.title);
+        self.pages
+            .push(vec![text(MARGIN, MARGIN + 14.0, 16.0, Anchor::Start, title)]);
+        self.column = 0;
+        self.top = MARGIN + TITLE_HEIGHT;
+        self.y = self.top;
+        self.bottom = self.top;
+    }
+    /// Reserves `height` points in a column and returns their top left corner. A block taller
+    /// than a whole column still gets one to itself on a continuation page.
+    fn place(&mut self, height: f64) -> (f64, f64) {
+        while self.y + height > PAGE_HEIGHT - MARGIN && self.y > MARGIN + TITLE_HEIGHT {
+            if self.column == 0 {
+                self.column = 1;
+                self.y = self.top;
+            } else {
+                self.new_page();
+            }
+        }
+        let corner = (MARGIN + self.column as f64 * (COLUMN_WIDTH + GUTTER), self.y);
+        self.y += height;
+        self.bottom = self.bottom.max(self.y);
+        corner
+    }
+    /// Reserves `height` points across both columns, below everything so far.
+    fn place_wide(&mut self, height: f64) -> f64 {
+        if self.bottom + height > PAGE_HEIGHT - MARGIN {
+            self.new_page();
+        }
+        let y = self.bottom;
+        self.bottom += height;
+        self.column = 1;
+        self.y = self.bottom;
+        y
+    }
+    /// Starts a pair of columns `gap` points below everything so far.
+    fn columns(&mut self, gap: f64) {
+        self.top = self.bottom + gap;
+        self.column = 0;
+        self.y = self.top;
+        self.bottom = self.top;
+    }
+    fn push(&mut self, shape: Shape) {
+        self.pages.last_mut().unwrap().push(shape);
+    }
+    fn finish(self) -> Vec<Page> {
+        self.pages
+            .into_iter()
+            .map(|shapes| Page {
+                width: PAGE_WIDTH,
+                height: PAGE_HEIGHT,
+                shapes,
+            })
+            .collect()
+    }
+}
+
+/// Draws the quote grid and clue list on letter-size pages, continuing the grid and then the
+/// clues on further pages as needed. The answer key fills in every letter.
+pub fn layout(acrostic: &Acrostic, title: &str, key: bool) -> Vec<Page> {
+    let mut flow = Flow::new(title);
+    for (row, cells) in acrostic.cells.chunks(QUOTE_WIDTH).enumerate() {
+        let y = flow.place_wide(CELL);
+        for (column, c) in cells.iter().enumerate() {
+            let i = row * QUOTE_WIDTH + column;
+            let x = MARGIN + column as f64 * CELL;
+            flow.push(Shape::Rect {
+                x,
+                y,
+                width: CELL,
+                height: CELL,
+                filled: *c == ' ',
+            });
+            if *c == ' ' {
+                continue;
+            }
+            if let (Some(number), Some(owner)) = (acrostic.numbers[i], acrostic.owners[i]) {
+                flow.push(text(x + 2.0, y + 8.0, 7.0, Anchor::Start, number.to_string()));
+                flow.push(text(x + CELL - 2.0, y + 8.0, 7.0, Anchor::End, clue_label(owner)));
+            }
+            if key || acrostic.numbers[i].is_none() {
+                let letter = c.to_string();
+                flow.push(text(x + CELL / 2.0, y + CELL * 0.8, 16.0, Anchor::Middle, letter));
+            }
+        }
+    }
+    flow.columns(24.0);
+    let per_row = (COLUMN_WIDTH / BLANK) as usize;
+    for (i, clue) in acrostic.clues.iter().enumerate() {
+        let clue_text = format!("{}. {}", clue_label(i), clue.clue.as_deref().unwrap_or_default());
+        let lines = wrap(&clue_text, chars_per_line(COLUMN_WIDTH, CLUE_SIZE));
+        let rows = clue.indices.len().div_ceil(per_row);
+        let height = lines.len() as f64 * CLUE_LINE + rows as f64 * BLANK_ROW + CLUE_GAP;
+        let (x, mut y) = flow.place(height);
+        for line in lines {
+            y += CLUE_LINE;
+            flow.push(text(x, y, CLUE_SIZE, Anchor::Start, line));
+        }
+        for (k, index) in clue.indices.iter().enumerate() {
+            let left = x + (k % per_row) as f64 * BLANK;
+            let middle = left + BLANK / 2.0;
+            let row = y + (k / per_row) as f64 * BLANK_ROW;
+            flow.push(Shape::Line {
+                x1: left + 2.0,
+                y1: row + 18.0,
+                x2: left + BLANK - 2.0,
+                y2: row + 18.0,
+            });
+            let number = acrostic.numbers[*index].unwrap_or_default();
+            flow.push(text(middle, row + 25.0, 6.0, Anchor::Middle, number.to_string()));
+            if key {
+                let letter = acrostic.cells[*index].to_string();
+                flow.push(text(middle, row + 16.0, 11.0, Anchor::Middle, letter));
+            }
+        }
+    }
+    if key {
+        let quote = format!("{} \u{2014} {}", acrostic.puzzle.quote, acrostic.puzzle.source);
+        for line in wrap(&quote, chars_per_line(PAGE_WIDTH - 2.0 * MARGIN, 10.0)) {
+            let y = flow.place_wide(13.0) + 13.0;
+            flow.push(text(MARGIN, y, 10.0, Anchor::Start, line));
+        }
+    }
+    flow.finish()
+}
+
+/// The puzzle's pages and the answer key's pages.
+pub fn pages(puzzle: &Puzzle, title: &str) -> anyhow::Result<(Vec<Page>, Vec<Page>)> {
+    let acrostic = Acrostic::new(puzzle)?;
+    Ok((
+        layout(&acrostic, title, false),
+        layout(&acrostic, &format!("{}: answers", title), true),
+    ))
+}
+
+/// `puzzle12.svg`, then `puzzle12-2.svg` and so on for continuation pages.
+fn svg_name(stem: &str, page: usize) -> String {
+    match page {
+        0 => format!("{}.svg", stem),
+        _ => format!("{}-{}.svg", stem, page + 1),
+    }
+}
+
+fn render_path(name: &str) -> PathBuf {
+    PACKAGE_PATH.join("build/render").join(name)
+}
+
+pub async fn render_puzzle(pindex: usize, pdf: bool) -> anyhow::Result<()> {
+    let puzzle = Puzzle::read(pindex, Stage::Chat).await?;
+    let (pages, key) = pages(&puzzle, &format!("Acrostic {}", pindex))?;
+    let stems = [format!("puzzle{}", pindex), format!("puzzle{}-key", pindex)];
+    for (stem, pages) in stems.iter().zip([&pages, &key]) {
+        for (i, page) in pages.iter().enumerate() {
+            write_path(&render_path(&svg_name(stem, i)), svg::render(page).as_bytes()).await?;
+        }
+    }
+    if pdf {
+        let all: Vec<Page> = pages.into_iter().chain(key).collect();
+        write_path(&render_path(&format!("puzzle{}.pdf", pindex)), &pdf::render(&all)).await?;
+    }
+    Ok(())
+}
+
+/// Writes the puzzle pages and answer key pages of each puzzle to `build/render/`.
+pub async fn render_puzzles(args: &RenderArgs) -> anyhow::Result<()> {
+    create_dir_all(PACKAGE_PATH.join("build/render")).await?;
+    let mut report = Report::new();
+    for pindex in args.puzzle_indices() {
+        report.push(pindex, Outcome::from_result(render_puzzle(pindex, args.pdf).await));
+    }
+    report.print_summary();
+    if report.failures() > 0 {
+        return Err(anyhow!("{} puzzles failed", report.failures()));
+    }
+    Ok(())
+}
+
+#[test]
+fn test_layout_fits_pages() {
+    use acrostic_core::puzzle::PuzzleState;
+
+    use crate::export::clued_puzzles;
+    use crate::import::Draft;
+    // A quote whose grid alone runs past the first page.
+    let mut long = Draft {
+        quote: "tan ".repeat(100).trim_end().to_string(),
+        source: "t ".repeat(100).trim_end().to_string(),
+        quote_letters: "TAN ".repeat(100).trim_end().to_string(),
+        answers: vec!["TAN".to_string(); 100],
+    }
+    .to_puzzle()
+    .unwrap();
+    long.state = PuzzleState::Clued;
+    for clue in long.clues.as_mut().unwrap() {
+        clue.clue = Some("Brown from the sun".to_string());
+    }
+    assert!(pages(&long, "Test").unwrap().0.len() > 1);
+    for (pindex, puzzle) in clued_puzzles().into_iter().chain([(0, long)]) {
+        let (pages, key) = pages(&puzzle, "Test").unwrap();
+        for page in pages.iter().chain(&key) {
+            assert_eq!((page.width, page.height), (PAGE_WIDTH, PAGE_HEIGHT));
+            for shape in &page.shapes {
+                let (right, bottom) = match shape {
+                    Shape::Rect { x, y, width, height, .. } => (x + width, y + height),
+                    Shape::Line { x2, y2, .. } => (*x2, *y2),
+                    Shape::Text { x, y, size, anchor, text } => {
+                        let width = 0.6 * size * text.chars().count() as f64;
+                        match anchor {
+                            Anchor::Start => (x + width, *y),
+                            Anchor::Middle => (x + width / 2.0, *y),
+                            Anchor::End => (*x, *y),
+                        }
+                    }
+                };
+                assert!(right <= PAGE_WIDTH - MARGIN + 1e-6, "puzzle {}: {:?}", pindex, shape);
+                assert!(bottom <= PAGE_HEIGHT - MARGIN + 1e-6, "puzzle {}: {:?}", pindex, shape);
+            }
+        }
+    }
+}
+
+#[test]
+fn test_wrap() {
+    assert_eq!(wrap("a bb ccc dddd", 6), vec!["a bb", "ccc", "dddd"]);
+    assert_eq!(wrap("", 6), Vec::<String>::new());
+}
@@ -0,0 +1,146 @@
+use std::fmt::Write;
+
+use any_ascii::any_ascii;
+
+use crate::render::{Anchor, Page, Shape};
+
+/// The built-in Courier font only covers Latin-1 reliably, so other characters are
+/// transliterated, and anything left unprintable is dropped.
+fn printable(x: &str) -> String {
+    any_ascii(x).chars().filter(|c| c.is_ascii_graphic() || *c == ' ').collect()
+}
+
+/// Escapes a PDF string literal.
+fn escape(x: &str) -> String {
+    let mut out = String::new();
+    for c in x.chars() {
+        if matches!(c, '(' | ')' | '\\') {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out
+}
+
+/// The drawing operators for one page, flipping to PDF's bottom-left origin.
+fn content(page: &Page) -> String {
+    let mut out = String::new();
+    let flip = |y: f64| page.height - y;
+    writeln!(out, "0.75 w").unwrap();
+    for shape in &page.shapes {
+        match shape {
+            Shape::Rect {
+                x,
+                y,
+                width,
+                height,
+                filled,
+            } => {
+                let op = if *filled { "B" } else { "S" };
+                writeln!(out, "{} {} {} {} re {}", x, flip(y + height), width, height, op).unwrap();
+            }
+            Shape::Line { x1, y1, x2, y2 } => {
+                writeln!(out, "{} {} m {} {} l S", x1, flip(*y1), x2, flip(*y2)).unwrap();
+            }
+            Shape::Text {
+                x,
+                y,
+                size,
+                anchor,
+                text,
+            } => {
+                let text = printable(text);
+                let width = 0.6 * size * text.len() as f64;
+                let x = match anchor {
+                    Anchor::Start => *x,
+                    Anchor::Middle => x - width / 2.0,
+                    Anchor::End => x - width,
+                };
+                let text = escape(&text);
+                writeln!(out, "BT /F1 {} Tf {} {} Td ({}) Tj ET", size, x, flip(*y), text).unwrap();
+            }
+        }
+    }
+    out
+}
+
+/// Writes a PDF with one page per [Page], using only the standard Courier font.
+pub fn render(pages: &[Page]) -> Vec<u8> {
+    // 1: catalog, 2: page tree, 3: font, then a page and its content stream per page.
+    let page_id = |i: usize| 4 + 2 * i;
+    let mut objects = vec![
+        "<< /Type /Catalog /Pages 2 0 R >>".to_string(),
+        format!(
+            "<< /Type /Pages /Kids [{}] /Count {} >>",
+            (0..pages.len()).map(|i| format!("{} 0 R", page_id(i))).collect::<Vec<_>>().join(" "),
+            pages.len()
+        ),
+        "<< /Type /Font /Subtype /Type1 /BaseFont /Courier /Encoding /WinAnsiEncoding >>"
+            .to_string(),
+    ];
+    for (i, page) in pages.iter().enumerate() {
+        objects.push(format!(
+            "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {} {}] \
+             /Resources << /Font << /F1 3 0 R >> >> /Contents {} 0 R >>",
+            page.width,
+            page.height,
+            page_id(i) + 1
+        ));
+        let content = content(page);
+        objects.push(format!(
+            "<< /Length {} >>\nstream\n{}endstream",
+            content.len(),
+            content
+        ));
+    }
+    let mut out = b"%PDF-1.4\n".to_vec();
+    let mut offsets = vec![];
+    for (i, object) in objects.iter().enumerate() {
+        offsets.push(out.len());
+        out.extend_from_slice(format!("{} 0 obj\n{}\nendobj\n", i + 1, object).as_bytes());
+    }
+    let xref = out.len();
+    let mut trailer = format!("xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1);
+    for offset in offsets {
+        writeln!(trailer, "{:010} 00000 n ", offset).unwrap();
+    }
+    write!(
+        trailer,
+        "trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{}\n%%EOF\n",
+        objects.len() + 1,
+        xref
+    )
+    .unwrap();
+    out.extend_from_slice(trailer.as_bytes());
+    out
+}
+
+#[test]
+fn test_render_pdf() {
+    use crate::export::clued_puzzles;
+    use crate::render::pages;
+    let Some((_, puzzle)) = clued_puzzles().into_iter().next() else {
+        return;
+    };
+    let (pages, key) = pages(&puzzle, "Test").unwrap();
+    let count = pages.len() + key.len();
+    let pdf = render(&pages.into_iter().chain(key).collect::<Vec<_>>());
+    let pdf = String::from_utf8(pdf).unwrap();
+    assert!(pdf.starts_with("%PDF-1.4\n"));
+    // Every xref entry points at the object it numbers.
+    let xref = pdf[pdf.rfind("startxref\n").unwrap() + 10..].lines().next().unwrap();
+    let entries = pdf[xref.parse::<usize>().unwrap()..].lines().skip(3);
+    for (i, entry) in entries.take_while(|x| !x.starts_with("trailer")).enumerate() {
+        let offset: usize = entry[..10].parse().unwrap();
+        assert!(pdf[offset..].starts_with(&format!("{} 0 obj", i + 1)));
+    }
+    assert_eq!(pdf.matches("/Type /Page ").count(), count);
+    assert_eq!(escape(&printable("a(b)\\c\u{2014}")), "a\\(b\\)\\\\c-");
+    // Escapes don't count towards the width of anchored text.
+    let page = Page {
+        width: 100.0,
+        height: 100.0,
+        shapes: vec![crate::render::text(50.0, 50.0, 10.0, Anchor::End, "(a)")],
+    };
+    assert!(content(&page).contains("BT /F1 10 Tf 32 50 Td (\\(a\\)) Tj ET"));
+}
@@ -0,0 +1,93 @@
+use std::fmt::Write;
+
+use crate::render::{Anchor, Page, Shape};
+
+fn escape(x: &str) -> String {
+    x.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+pub fn render(page: &Page) -> String {
+    let mut out = String::new();
+    writeln!(
+        out,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{0}pt" height="{1}pt" viewBox="0 0 {0} {1}">"#,
+        page.width, page.height
+    )
+    .unwrap();
+    writeln!(out, r#"<rect width="100%" height="100%" fill="white"/>"#).unwrap();
+    writeln!(out, r#"<g font-family="Courier, monospace" stroke-width="0.75">"#).unwrap();
+    for shape in &page.shapes {
+        match shape {
+            Shape::Rect {
+                x,
+                y,
+                width,
+                height,
+                filled,
+            } => {
+                let fill = if *filled { "black" } else { "none" };
+                writeln!(
+                    out,
+                    r#"<rect x="{}" y="{}" width="{}" height="{}" fill="{}" stroke="black"/>"#,
+                    x, y, width, height, fill
+                )
+                .unwrap();
+            }
+            Shape::Line { x1, y1, x2, y2 } => {
+                writeln!(
+                    out,
+                    r#"<line x1="{}" y1="{}" x2="{}" y2="{}" stroke="black"/>"#,
+                    x1, y1, x2, y2
+                )
+                .unwrap();
+            }
+            Shape::Text {
+                x,
+                y,
+                size,
+                anchor,
+                text,
+            } => {
+                let anchor = match anchor {
+                    Anchor::Start => "start",
+                    Anchor::Middle => "middle",
+                    Anchor::End => "end",
+                };
+                writeln!(
+                    out,
+                    r#"<text x="{}" y="{}" font-size="{}" text-anchor="{}">{}</text>"#,
+                    x,
+                    y,
+                    size,
+                    anchor,
+                    escape(text)
+                )
+                .unwrap();
+            }
+        }
+    }
+    writeln!(out, "</g>").unwrap();
+    writeln!(out, "</svg>").unwrap();
+    out
+}
+
+#[test]
+fn test_render_svg() {
+    let page = Page {
+        width: 100.0,
+        height: 50.0,
+        shapes: vec![Shape::Text {
+            x: 1.0,
+            y: 2.0,
+            size: 9.0,
+            anchor: Anchor::Middle,
+            text: "Q&A".to_string(),
+        }],
+    };
+    let svg = render(&page);
+    assert!(svg.contains(r#"viewBox="0 0 100 50""#));
+    assert!(svg.contains(r#"<text x="1" y="2" font-size="9" text-anchor="middle">Q&amp;A</text>"#));
+}
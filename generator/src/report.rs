@@ -0,0 +1,152 @@
+use std::any::Any;
+use std::fmt::{Display, Formatter};
+use std::future::Future;
+use std::io;
+use std::panic::AssertUnwindSafe;
+
+use futures::FutureExt;
+
+use crate::pipeline::Stage;
+use crate::quote::QuoteRejected;
+
+/// The result of running one stage on one puzzle.
+#[derive(Debug)]
+pub enum Outcome {
+    Done,
+    /// The stage's input does not exist, so there was nothing to do.
+    Skipped(String),
+    /// The quote did not meet the quote filter.
+    Rejected(String),
+    Failed(String),
+}
+
+impl Outcome {
+    pub fn from_result(result: anyhow::Result<()>) -> Self {
+        match result {
+            Ok(()) => Outcome::Done,
+            Err(e) => {
+                if let Some(rejected) = e.downcast_ref::<QuoteRejected>() {
+                    Outcome::Rejected(rejected.0.clone())
+                } else if e.downcast_ref::<io::Error>()
+                    .map_or(false, |x| x.kind() == io::ErrorKind::NotFound)
+                {
+                    Outcome::Skipped(e.to_string())
+                } else {
+                    Outcome::Failed(format!("{:#}", e))
+                }
+            }
+        }
+    }
+    /// Runs a stage, turning panics into failures so one bad puzzle cannot abort a batch.
+    pub async fn catch(fut: impl Future<Output = anyhow::Result<()>>) -> Self {
+        match AssertUnwindSafe(fut).catch_unwind().await {
+            Ok(result) => Self::from_result(result),
+            Err(panic) => Outcome::Failed(format!("panicked: {}", panic_message(&*panic))),
+        }
+    }
+    pub fn is_failed(&self) -> bool {
+        matches!(self, Outcome::Failed(_))
+    }
+}
+
+fn panic_message(panic: &(dyn Any + Send)) -> &str {
+    if let Some(x) = panic.downcast_ref::<&'static str>() {
+        x
+    } else if let Some(x) = panic.downcast_ref::<String>() {
+        x
+    } else {
+        "unknown panic"
+    }
+}
+
+impl Display for Outcome {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Outcome::Done => write!(f, "done"),
+            Outcome::Skipped(e) => write!(f, "skipped  {}", e),
+            Outcome::Rejected(e) => write!(f, "rejected {}", e),
+            Outcome::Failed(e) => write!(f, "failed   {}", e),
+        }
+    }
+}
+
+struct Row {
+    puzzle: usize,
+    reached: Option<Stage>,
+    outcome: Outcome,
+}
+
+/// Per-puzzle outcomes of a batch run.
+#[derive(Default)]
+pub struct Report {
+    rows: Vec<Row>,
+}
+
+impl Report {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn push(&mut self, puzzle: usize, outcome: Outcome) {
+        self.push_reached(puzzle, None, outcome);
+    }
+    /// Records an outcome along with the furthest stage the puzzle has completed.
+    pub fn push_reached(&mut self, puzzle: usize, reached: Option<Stage>, outcome: Outcome) {
+        self.rows.push(Row {
+            puzzle,
+            reached,
+            outcome,
+        });
+    }
+    pub fn failures(&self) -> usize {
+        self.rows.iter().filter(|x| x.outcome.is_failed()).count()
+    }
+    /// Prints one row per completed or failed puzzle; skipped and rejected puzzles are only counted.
+    pub fn print_summary(&mut self) {
+        self.rows.sort_by_key(|x| x.puzzle);
+        let staged = self.rows.iter().any(|x| x.reached.is_some());
+        let mut done = 0;
+        let mut skipped = 0;
+        let mut rejected = 0;
+        let mut failed = 0;
+        if staged {
+            eprintln!("{:>8}  {:<8}  {}", "puzzle", "reached", "status");
+        } else {
+            eprintln!("{:>8}  {}", "puzzle", "status");
+        }
+        for row in &self.rows {
+            match &row.outcome {
+                Outcome::Done => done += 1,
+                Outcome::Skipped(_) => {
+                    skipped += 1;
+                    continue;
+                }
+                Outcome::Rejected(_) => {
+                    rejected += 1;
+                    continue;
+                }
+                Outcome::Failed(_) => failed += 1,
+            }
+            if staged {
+                let reached = row.reached.map_or("-".to_string(), |x| x.to_string());
+                eprintln!("{:>8}  {:<8}  {}", row.puzzle, reached, row.outcome);
+            } else {
+                eprintln!("{:>8}  {}", row.puzzle, row.outcome);
+            }
+        }
+        eprintln!(
+            "{} done, {} skipped, {} rejected, {} failed",
+            done, skipped, rejected, failed
+        );
+    }
+}
+
+#[test]
+fn test_outcome() {
+    let missing = io::Error::new(io::ErrorKind::NotFound, "Cannot read \"stage0.json\"");
+    assert!(matches!(Outcome::from_result(Err(missing.into())), Outcome::Skipped(_)));
+    let bad = io::Error::new(io::ErrorKind::InvalidInput, "bad quote");
+    assert!(matches!(Outcome::from_result(Err(bad.into())), Outcome::Failed(_)));
+    assert!(matches!(Outcome::from_result(Ok(())), Outcome::Done));
+    let rejected = QuoteRejected("quote has 12 letters".to_string());
+    assert!(matches!(Outcome::from_result(Err(rejected.into())), Outcome::Rejected(_)));
+}
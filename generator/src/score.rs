@@ -0,0 +1,251 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use acrostic_core::letter::LetterSet;
+use acrostic_core::puzzle::DifficultyLevel;
+use tokio::fs;
+
+use crate::dict::FlatWord;
+use crate::lemma::{Lemma, LEMMA};
+use crate::model::{Model, Part};
+use crate::util::lazy_async::CloneError;
+use crate::PACKAGE_PATH;
+
+/// How good a dictionary word is as an answer. Higher is better.
+pub trait WordScorer: Send + Sync {
+    /// `None` rules the word out entirely.
+    fn score(&self, word: &FlatWord) -> Option<f64>;
+}
+
+/// Prefers common words: the log of the word frequency, scaled to `0..=1` over the range of
+/// frequencies being scored.
+pub struct Familiarity {
+    log_min: f64,
+    log_max: f64,
+}
+
+impl Familiarity {
+    pub fn new<'a>(words: impl IntoIterator<Item = &'a FlatWord>) -> Self {
+        let (min, max) = words
+            .into_iter()
+            .map(|x| x.frequency.max(1))
+            .fold((u64::MAX, 1), |(min, max), x| (min.min(x), max.max(x)));
+        Familiarity {
+            log_min: (min.min(max) as f64).ln(),
+            log_max: (max as f64).ln(),
+        }
+    }
+}
+
+impl WordScorer for Familiarity {
+    fn score(&self, word: &FlatWord) -> Option<f64> {
+        let log = (word.frequency.max(1) as f64).ln();
+        if self.log_max <= self.log_min {
+            return Some(1.0);
+        }
+        Some(((log - self.log_min) / (self.log_max - self.log_min)).clamp(0.0, 1.0))
+    }
+}
+
+/// Prefers longer words, from 0 at six letters or fewer to 1 at sixteen. The scale is fixed
+/// whatever the manifest's `min_letters`, so difficulty scores stay comparable across tries.
+pub struct Length;
+
+impl Length {
+    fn of(word: &FlatWord) -> f64 {
+        ((word.letter_vec.len() as f64 - 6.0) / 10.0).clamp(0.0, 1.0)
+    }
+}
+
+impl WordScorer for Length {
+    fn score(&self, word: &FlatWord) -> Option<f64> {
+        Some(Self::of(word))
+    }
+}
+
+/// Rejects words rarer than `min_frequency`.
+pub struct ObscurityCeiling {
+    pub min_frequency: u64,
+}
+
+impl ObscurityCeiling {
+    /// Keeps only the `max_rank` most frequent of `words`.
+    pub fn by_rank<'a>(words: impl IntoIterator<Item = &'a FlatWord>, max_rank: usize) -> Self {
+        let mut frequencies: Vec<u64> = words.into_iter().map(|x| x.frequency).collect();
+        frequencies.sort_unstable_by(|a, b| b.cmp(a));
+        let min_frequency = match max_rank.checked_sub(1) {
+            Some(last) => frequencies.get(last).copied().unwrap_or(0),
+            None => u64::MAX,
+        };
+        ObscurityCeiling { min_frequency }
+    }
+}
+
+impl WordScorer for ObscurityCeiling {
+    fn score(&self, word: &FlatWord) -> Option<f64> {
+        (word.frequency >= self.min_frequency).then_some(0.0)
+    }
+}
+
+/// Prefers words that use up hard letters, which keeps the search from stranding a `Q` or `Z`.
+/// This was the search's only ranking before scorers existed.
+pub struct LetterRarity;
+
+impl WordScorer for LetterRarity {
+    fn score(&self, word: &FlatWord) -> Option<f64> {
+        let letters: LetterSet = word.letters;
+        Some(letters.scrabble_score() as f64 / letters.count().max(1) as f64 / 10.0)
+    }
+}
+
+/// Prefers content words over other parts of speech, and marks down proper nouns, using the
+/// tags in the word embedding model.
+pub struct PartOfSpeech {
+    parts: HashMap<String, Vec<Part>>,
+}
+
+impl PartOfSpeech {
+    pub fn new(model: &Model) -> Self {
+        let mut parts: HashMap<String, Vec<Part>> = HashMap::new();
+        for word in &model.words {
+            parts.entry(word.word.to_lowercase()).or_default().push(word.part);
+        }
+        PartOfSpeech { parts }
+    }
+    fn preference(part: Part) -> f64 {
+        match part {
+            Part::Noun | Part::Verb | Part::Adj => 1.0,
+            Part::Adv => 0.7,
+            Part::Num | Part::Intj => 0.3,
+            Part::Propn | Part::X | Part::Sym => 0.0,
+        }
+    }
+}
+
+impl WordScorer for PartOfSpeech {
+    fn score(&self, word: &FlatWord) -> Option<f64> {
+        match self.parts.get(word.word.as_str()) {
+            None => Some(0.5),
+            Some(parts) => Some(parts.iter().map(|x| Self::preference(*x)).fold(0.0, f64::max)),
+        }
+    }
+}
+
+/// Marks down inflected forms ending in `s`, which are mostly plurals.
+pub struct Plurals {
+    lemma: Arc<Lemma>,
+}
+
+impl Plurals {
+    pub fn new(lemma: Arc<Lemma>) -> Self {
+        Plurals { lemma }
+    }
+}
+
+impl WordScorer for Plurals {
+    fn score(&self, word: &FlatWord) -> Option<f64> {
+        let word = word.word.as_str();
+        let inflected = word.ends_with('s') && self.lemma.canonicals(word).iter().any(|x| x != word);
+        Some(if inflected { 0.0 } else { 1.0 })
+    }
+}
+
+/// A weighted sum of other scorers. Rejected by any scorer means rejected by all. A negative
+/// weight turns a preference around.
+#[derive(Default)]
+pub struct Weighted(Vec<(f64, Box<dyn WordScorer>)>);
+
+impl Weighted {
+    pub fn with(mut self, weight: f64, scorer: impl WordScorer + 'static) -> Self {
+        self.0.push((weight, Box::new(scorer)));
+        self
+    }
+}
+
+impl WordScorer for Weighted {
+    fn score(&self, word: &FlatWord) -> Option<f64> {
+        let mut total = 0.0;
+        for (weight, scorer) in &self.0 {
+            total += weight * scorer.score(word)?;
+        }
+        Some(total)
+    }
+}
+
+/// How many of the most frequent trie words are answers at each difficulty. Hard puzzles may
+/// use the whole trie vocabulary, which `DictManifest::max_rank` bounds.
+pub fn max_rank(target: DifficultyLevel) -> usize {
+    match target {
+        DifficultyLevel::Easy => 4000,
+        DifficultyLevel::Medium => 9000,
+        DifficultyLevel::Hard => usize::MAX,
+    }
+}
+
+/// The scorer `add_answers` uses to steer towards `target`. Part-of-speech preference is
+/// included when the embedding model is present under `data/`.
+pub async fn default_scorer<'a>(
+    words: impl IntoIterator<Item = &'a FlatWord>,
+    target: DifficultyLevel,
+) -> anyhow::Result<Arc<dyn WordScorer>> {
+    let words: Vec<&FlatWord> = words.into_iter().collect();
+    let (familiarity, length, rarity) = match target {
+        DifficultyLevel::Easy => (3.0, -1.0, 0.5),
+        DifficultyLevel::Medium => (1.0, 0.0, 1.0),
+        DifficultyLevel::Hard => (-1.0, 1.0, 2.0),
+    };
+    let mut scorer = Weighted::default()
+        .with(familiarity, Familiarity::new(words.iter().copied()))
+        .with(length, Length)
+        .with(rarity, LetterRarity)
+        .with(0.5, Plurals::new(LEMMA.get().await.clone_error_static()?.clone()))
+        .with(1.0, ObscurityCeiling::by_rank(words, max_rank(target)));
+    let model_path = PACKAGE_PATH.join("data/223/model.bin");
+    if let Ok(model) = fs::read(&model_path).await {
+        scorer = scorer.with(0.5, PartOfSpeech::new(&Model::new(&model)));
+    }
+    Ok(Arc::new(scorer))
+}
+
+/// How hard a set of answers is, from 0 to 1, whatever difficulty was targeted: mostly how
+/// unfamiliar the words are, plus their length and letter rarity.
+pub fn difficulty_score(words: &[&FlatWord], familiarity: &Familiarity) -> f64 {
+    if words.is_empty() {
+        return 0.0;
+    }
+    let total: f64 = words
+        .iter()
+        .map(|word| {
+            let unfamiliar = 1.0 - familiarity.score(word).unwrap_or(0.0);
+            // Scrabble points per letter run from 1 to about 4 in practice.
+            let rarity = ((LetterRarity.score(word).unwrap_or(0.0) * 10.0 - 1.0) / 3.0).clamp(0.0, 1.0);
+            0.6 * unfamiliar + 0.2 * Length::of(word) + 0.2 * rarity
+        })
+        .sum();
+    total / words.len() as f64
+}
+
+#[test]
+fn test_scorers() {
+    use crate::dict::test_dict;
+    let words = test_dict(&[("common", 1_000_000), ("middle", 10_000), ("arcane", 100)]);
+    let familiarity = Familiarity::new(words.iter());
+    let round = |x: f64| (x * 1000.0).round() / 1000.0;
+    let scores: Vec<_> = words.iter().map(|x| round(familiarity.score(x).unwrap())).collect();
+    assert_eq!(scores, vec![1.0, 0.5, 0.0]);
+    let scorer = Weighted::default()
+        .with(2.0, familiarity)
+        .with(1.0, ObscurityCeiling { min_frequency: 1000 });
+    let scores: Vec<_> = words.iter().map(|x| scorer.score(x).map(round)).collect();
+    assert_eq!(scores, vec![Some(2.0), Some(1.0), None]);
+    let ceiling = |max_rank| ObscurityCeiling::by_rank(words.iter(), max_rank).min_frequency;
+    assert_eq!(ceiling(1), 1_000_000);
+    assert_eq!(ceiling(2), 10_000);
+    assert_eq!(ceiling(usize::MAX), 0);
+    assert_eq!(ceiling(0), u64::MAX);
+    let familiarity = Familiarity::new(words.iter());
+    let common = &words[0];
+    let arcane = &words[2];
+    assert!(difficulty_score(&[common], &familiarity) < difficulty_score(&[arcane], &familiarity));
+    assert_eq!(difficulty_score(&[], &familiarity), 0.0);
+}
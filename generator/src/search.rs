@@ -1,47 +1,419 @@
-use futures::{stream, SinkExt, StreamExt};
+use futures::{future, stream, SinkExt, StreamExt};
 use std::cell::{Cell, RefCell};
 use std::collections::{HashMap, HashSet};
 use std::fmt::Write;
 use std::io::ErrorKind;
 use std::mem::swap;
+use std::path::PathBuf;
+use std::str::FromStr;
 use std::sync::atomic::AtomicUsize;
 use std::sync::atomic::Ordering::Relaxed;
 use std::sync::Arc;
-use std::time::Instant;
+use std::fmt::{self, Display, Formatter};
+use std::time::{Duration, Instant};
 use std::{io, iter};
 
 use acrostic_core::letter::{Letter, LetterMap, LetterSet};
+use anyhow::anyhow;
+use clap::ValueEnum;
 use itertools::{max, Itertools};
 use ordered_float::{NotNan, OrderedFloat};
 use rand::rngs::StdRng;
-use rand::seq::SliceRandom;
+use rand::seq::{index, SliceRandom};
 use rand::{thread_rng, Rng, SeedableRng};
 use rand_xorshift::XorShiftRng;
 use safe_once_map::sync::OnceLockMap;
+use serde::Deserialize;
+use tokio::task::JoinHandle;
 
 // use crate::trie::Trie;
+use crate::diagnose::{explain_failure, Diagnosis};
 use crate::dict::FlatWord;
+use crate::exact::{ExactOutcome, ExactSolver};
 use crate::model::{Model, Word};
-use crate::puzzle::{Clue, Puzzle};
+use crate::pipeline::Stage;
+use crate::provenance::ARTIFACT_HASHES;
+use crate::puzzle::{Alternative, Clue, Difficulty, DifficultyLevel, Puzzle, PuzzleFile};
+use crate::quote::QuoteRejected;
+use crate::score::{default_scorer, difficulty_score, max_rank, Familiarity, WordScorer};
 use crate::trie_table::{FlatTrieTable, FLAT_TRIE_TABLE};
+use crate::util::interrupt::is_interrupted;
 use crate::util::lazy_async::CloneError;
+use crate::{read_path_to_string, PUZZLES_PATH};
+
+/// Limits on the lengths of the answers in a solution.
+#[derive(Debug, Clone)]
+pub struct LengthConstraints {
+    pub min_len: usize,
+    pub max_len: usize,
+    /// The mean answer length is fixed by the quote and source (every quote letter is used
+    /// once, and there is one answer per source letter), so this rejects puzzles up front
+    /// rather than steering the search.
+    pub target_mean: Option<f64>,
+    /// Answers longer than this count towards `max_long`.
+    pub long_len: usize,
+    pub max_long: usize,
+}
+
+/// How far the mean answer length may stray from [LengthConstraints::target_mean].
+pub const MEAN_TOLERANCE: f64 = 1.0;
+
+impl Default for LengthConstraints {
+    fn default() -> Self {
+        LengthConstraints {
+            min_len: 2,
+            max_len: usize::MAX,
+            target_mean: None,
+            long_len: 10,
+            max_long: usize::MAX,
+        }
+    }
+}
+
+impl LengthConstraints {
+    pub fn check_mean(&self, quote_letters: usize, answers: usize) -> Result<(), QuoteRejected> {
+        let Some(target) = self.target_mean else {
+            return Ok(());
+        };
+        let mean = quote_letters as f64 / answers.max(1) as f64;
+        if (mean - target).abs() > MEAN_TOLERANCE {
+            return Err(QuoteRejected(format!(
+                "mean answer length {:.1} is not within {} of {}",
+                mean, MEAN_TOLERANCE, target
+            )));
+        }
+        Ok(())
+    }
+    /// Whether `words` may join the rest of a solution in `placed`, where the slots being
+    /// filled are empty.
+    fn allows(&self, placed: &[LetterSet], words: &[LetterSet]) -> bool {
+        let long = placed
+            .iter()
+            .chain(words)
+            .filter(|x| x.count() > self.long_len)
+            .count();
+        words.iter().all(|x| x.count() <= self.max_len) && long <= self.max_long
+    }
+    fn is_satisfied(&self, words: &[LetterSet]) -> bool {
+        words.iter().all(|x| (self.min_len..=self.max_len).contains(&x.count()))
+            && self.allows(words, &[])
+    }
+}
+
+/// An answer an editor wants at one position of the source.
+#[derive(Debug, Clone, Eq, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PinnedAnswer {
+    /// Index into the source letters, from 0.
+    pub position: usize,
+    pub answer: String,
+}
+
+impl FromStr for PinnedAnswer {
+    type Err = anyhow::Error;
+    /// Parses `POSITION=WORD`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (position, answer) = s
+            .split_once('=')
+            .ok_or_else(|| anyhow!("expected POSITION=WORD, got {:?}", s))?;
+        Ok(PinnedAnswer {
+            position: position
+                .trim()
+                .parse()
+                .map_err(|e| anyhow!("invalid position {:?}: {}", position, e))?,
+            answer: answer.trim().to_string(),
+        })
+    }
+}
+
+/// Answers an editor has fixed or ruled out for a puzzle. Read from `answers.toml` in the
+/// puzzle's directory when present, for example:
+///
+/// ```toml
+/// exclude = ["moist"]
+///
+/// [[pin]]
+/// position = 0
+/// answer = "shakespeare"
+/// ```
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AnswerConstraints {
+    pub pin: Vec<PinnedAnswer>,
+    /// Words never used as answers, in any case.
+    pub exclude: Vec<String>,
+}
+
+impl AnswerConstraints {
+    pub fn from_toml(contents: &str) -> anyhow::Result<Self> {
+        Ok(toml::from_str(contents)?)
+    }
+    pub fn path(pindex: usize) -> PathBuf {
+        PUZZLES_PATH.join(format!("{}", pindex)).join("answers.toml")
+    }
+    /// The puzzle's `answers.toml`, or no constraints if it has none.
+    pub async fn load(pindex: usize) -> anyhow::Result<Self> {
+        let path = Self::path(pindex);
+        match read_path_to_string(&path).await {
+            Ok(contents) => Self::from_toml(&contents).map_err(|e| anyhow!("{:?}: {}", path, e)),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+    /// Adds `other`'s constraints; its pins replace any at the same positions.
+    pub fn extend(&mut self, other: &AnswerConstraints) {
+        self.pin.retain(|x| other.pin.iter().all(|y| y.position != x.position));
+        self.pin.extend(other.pin.iter().cloned());
+        self.exclude.extend(other.exclude.iter().cloned());
+    }
+    pub fn is_excluded(&self, word: &str) -> bool {
+        self.exclude.iter().any(|x| x.eq_ignore_ascii_case(word))
+    }
+}
+
+/// How [add_answers] looks for a solution.
+#[derive(ValueEnum, Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum SolverMode {
+    /// Randomized hill-climbing from many seeds.
+    Anneal,
+    /// Complete backtracking, which can prove a quote has no solution.
+    Exact,
+    /// Backtracking first, to reject impossible quotes cheaply, then annealing for a
+    /// better-scoring solution.
+    #[default]
+    Auto,
+}
+
+/// Nodes the exact solver may visit before giving up.
+pub const DEFAULT_EXACT_BUDGET: usize = 100_000;
+
+/// Seeds tried per puzzle before annealing gives up.
+const MAX_SEEDS: u64 = 1000;
+
+/// When a puzzle's search must stop: at its time limit, or once Ctrl-C is pressed.
+#[derive(Debug, Copy, Clone)]
+pub struct Deadline(Option<Instant>);
+
+impl Deadline {
+    pub fn after(limit: Option<Duration>) -> Self {
+        Deadline(limit.map(|x| Instant::now() + x))
+    }
+    pub fn is_past(&self) -> bool {
+        is_interrupted() || self.0.is_some_and(|x| Instant::now() >= x)
+    }
+}
+
+/// Counters the search updates as it runs, for progress reports and failure diagnoses.
+#[derive(Default)]
+struct Counters {
+    seeds: AtomicUsize,
+    solutions: AtomicUsize,
+    /// Seeds that finished without a solution. Counted on its own, since `seeds` and
+    /// `solutions` are not updated together.
+    failed: AtomicUsize,
+    /// Quote letters left over by the closest attempt so far.
+    best_remainder: AtomicUsize,
+    /// Calls to [Search::search_smallest_subset] that missed its cache.
+    misses: AtomicUsize,
+    /// Nodes the exact solver has searched.
+    exact_nodes: AtomicUsize,
+    /// How many failed seeds left each letter unused.
+    leftover: LetterMap<AtomicUsize>,
+}
+
+/// A snapshot of how a search is going.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchProgress {
+    pub elapsed: Duration,
+    pub exact_nodes: usize,
+    pub seeds: usize,
+    pub solutions: usize,
+    /// `None` until a seed has finished.
+    pub best_remainder: Option<usize>,
+    /// The fraction of pair searches answered from the cache.
+    pub cache_hit_rate: f64,
+}
+
+impl Display for SearchProgress {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "elapsed={:.0?} exact_nodes={} seeds={} solutions={} best_remainder={} \
+             cache_hits={:.0}%",
+            self.elapsed,
+            self.exact_nodes,
+            self.seeds,
+            self.solutions,
+            self.best_remainder.map_or("-".to_string(), |x| x.to_string()),
+            100.0 * self.cache_hit_rate
+        )
+    }
+}
+
+/// Settings for [add_answers].
+#[derive(Debug, Clone)]
+pub struct SearchOptions {
+    pub difficulty: DifficultyLevel,
+    pub lengths: LengthConstraints,
+    pub solver: SolverMode,
+    pub exact_budget: usize,
+    /// Added to each puzzle's own `answers.toml`.
+    pub answers: AnswerConstraints,
+    /// Wall-clock budget for each puzzle, covering both solvers.
+    pub time_limit: Option<Duration>,
+    /// How often to print a [SearchProgress] while searching.
+    pub progress_interval: Option<Duration>,
+    /// How many distinct answer sets to keep in the puzzle's `alternatives`; only annealing
+    /// finds more than one.
+    pub alternatives: usize,
+}
+
+impl Default for SearchOptions {
+    fn default() -> Self {
+        SearchOptions {
+            difficulty: DifficultyLevel::default(),
+            lengths: LengthConstraints::default(),
+            solver: SolverMode::default(),
+            exact_budget: DEFAULT_EXACT_BUDGET,
+            answers: AnswerConstraints::default(),
+            time_limit: None,
+            progress_interval: None,
+            alternatives: 1,
+        }
+    }
+}
+
+/// How many candidate words [Search::optimize_k] tries before giving up on a move.
+const COMPOSE_BUDGET: usize = 64;
+
+/// How many random triples of answers [Search::optimize] tries to swap at once when no pair
+/// swap helps.
+const TRIPLE_SAMPLES: usize = 32;
 
 pub struct Search {
     table: &'static FlatTrieTable,
+    lengths: LengthConstraints,
+    /// The best-scoring word for each first letter and letter multiset in the tries. Multisets
+    /// whose words are all rejected by the scorer are missing.
+    scores: HashMap<(Letter, LetterSet), (f64, &'static FlatWord)>,
+    /// Answers fixed by the editor, by source position. The moves never change these slots.
+    pinned: Vec<Option<&'static FlatWord>>,
     cache: OnceLockMap<(Letter, Letter, LetterSet, usize), Vec<(LetterSet, LetterSet)>>,
     access: AtomicUsize,
+    counters: Counters,
+    deadline: Deadline,
+    start_time: Instant,
     quote: LetterSet,
     source: Vec<Letter>,
 }
 
 impl Search {
-    pub async fn new(quote: LetterSet, source: Vec<Letter>) -> anyhow::Result<Self> {
-        Ok(Search {
-            table: FLAT_TRIE_TABLE.get().await.clone_error_static()?,
+    pub async fn new(
+        quote: LetterSet,
+        source: Vec<Letter>,
+        scorer: &dyn WordScorer,
+        lengths: LengthConstraints,
+        answers: &AnswerConstraints,
+        deadline: Deadline,
+    ) -> anyhow::Result<Self> {
+        let table: &'static FlatTrieTable = FLAT_TRIE_TABLE.get().await.clone_error_static()?;
+        Self::with_table(table, quote, source, scorer, lengths, answers, deadline)
+    }
+    /// A search over the tries of `table`, which need not be the one in `build/`.
+    pub fn with_table(
+        table: &'static FlatTrieTable,
+        quote: LetterSet,
+        source: Vec<Letter>,
+        scorer: &dyn WordScorer,
+        lengths: LengthConstraints,
+        answers: &AnswerConstraints,
+        deadline: Deadline,
+    ) -> anyhow::Result<Self> {
+        let mut scores = HashMap::<_, (f64, &'static FlatWord)>::new();
+        for word in table.words() {
+            if answers.is_excluded(&word.word) {
+                continue;
+            }
+            let (Some(first), Some(score)) = (word.letter_vec.first(), scorer.score(word)) else {
+                continue;
+            };
+            let best = scores.entry((*first, word.letters)).or_insert((score, word));
+            if score > best.0 {
+                *best = (score, word);
+            }
+        }
+        let mut search = Search {
+            table,
+            lengths,
+            scores,
+            pinned: vec![None; source.len()],
             cache: Default::default(),
             access: AtomicUsize::new(0),
+            counters: Counters {
+                best_remainder: AtomicUsize::new(usize::MAX),
+                ..Counters::default()
+            },
+            deadline,
+            start_time: Instant::now(),
             quote,
             source,
+        };
+        let mut pinned_letters = LetterSet::new();
+        for pin in &answers.pin {
+            let word = search
+                .dictionary_word(&pin.answer)
+                .ok_or_else(|| anyhow!("pinned answer {:?} is not in the dictionary", pin.answer))?;
+            if search.pinned(pin.position).is_some() {
+                return Err(anyhow!("position {} is pinned twice", pin.position));
+            }
+            search.pin(pin.position, word)?;
+            pinned_letters = pinned_letters + word.letters;
+        }
+        if !pinned_letters.is_subset(quote) {
+            let reason = "pinned answers use letters the quote lacks".to_string();
+            return Err(QuoteRejected(reason).into());
+        }
+        Ok(search)
+    }
+    /// The dictionary entry spelled `answer`, in any case.
+    pub fn dictionary_word(&self, answer: &str) -> Option<&'static FlatWord> {
+        self.table.dict.iter().find(|x| x.word.eq_ignore_ascii_case(answer))
+    }
+    /// Fixes the answer at `index` to `word`, so the moves leave it alone.
+    pub fn pin(&mut self, index: usize, word: &'static FlatWord) -> anyhow::Result<()> {
+        let first = self.source.get(index).ok_or_else(|| {
+            anyhow!(
+                "pinned position {} is past the source's {} letters",
+                index,
+                self.source.len()
+            )
+        })?;
+        if word.letter_vec.first() != Some(first) {
+            return Err(anyhow!("pinned answer {:?} does not start with {:?}", word.word, first));
+        }
+        if !(self.lengths.min_len..=self.lengths.max_len).contains(&word.letter_vec.len()) {
+            return Err(anyhow!("pinned answer {:?} breaks the length limits", word.word));
+        }
+        self.pinned[index] = Some(word);
+        Ok(())
+    }
+    pub fn unpin(&mut self, index: usize) {
+        self.pinned[index] = None;
+    }
+    pub fn pinned(&self, index: usize) -> Option<&'static FlatWord> {
+        self.pinned.get(index).copied().flatten()
+    }
+    /// A solution holding `words`, one per source letter, or `None` if they do not fit in the
+    /// quote.
+    pub fn solution(&self, words: Vec<LetterSet>, seed: u64) -> Option<Solution> {
+        let used = words.iter().fold(LetterSet::new(), |a, b| a + *b);
+        if words.len() != self.source.len() || !used.is_subset(self.quote) {
+            return None;
+        }
+        Some(Solution {
+            words,
+            remainder: self.quote - used,
+            rng: XorShiftRng::seed_from_u64(seed),
         })
     }
     fn start(&self, seed: u64) -> Option<Solution> {
@@ -50,7 +422,11 @@ impl Search {
         let words: Vec<LetterSet> = self
             .source
             .iter()
-            .map(|first| [first].into_iter().cloned().collect())
+            .zip(&self.pinned)
+            .map(|(first, pinned)| match pinned {
+                Some(word) => word.letters,
+                None => [first].into_iter().cloned().collect(),
+            })
             .collect();
         for word in words.iter() {
             if !word.is_subset(remainder) {
@@ -64,10 +440,22 @@ impl Search {
             rng,
         })
     }
+    fn is_pinned(&self, index: usize) -> bool {
+        self.pinned[index].is_some()
+    }
+    fn score(&self, index: usize, word: LetterSet) -> Option<f64> {
+        Some(self.scores.get(&(self.source[index], word))?.0)
+    }
+    /// The total score of the solution's words.
+    pub fn quality(&self, solution: &Solution) -> f64 {
+        (0..solution.words.len())
+            .map(|i| self.score(i, solution.words[i]).unwrap_or(0.0))
+            .sum()
+    }
     #[inline(never)]
     fn randomize1(&self, solution: &mut Solution, index: usize) {
         let old = solution.words[index];
-        if old.count() > 4 {
+        if old.count() > 4 && !self.is_pinned(index) {
             solution.set_word(index, LetterSet::new());
             let mut found = vec![];
             self.table.unary[self.source[index]].search_largest_subset(
@@ -75,6 +463,9 @@ impl Search {
                 old.count() - 1,
                 &mut found,
             );
+            found.retain(|x| {
+                self.score(index, *x).is_some() && self.lengths.allows(&solution.words, &[*x])
+            });
             if let Some(found) = found.choose(&mut solution.rng) {
                 solution.set_word(index, *found);
             } else {
@@ -92,6 +483,9 @@ impl Search {
     }
     #[inline(never)]
     fn optimize1(&self, solution: &mut Solution, index: usize) -> bool {
+        if self.is_pinned(index) {
+            return false;
+        }
         let old = solution.words[index];
         solution.set_word(index, LetterSet::new());
         let min_len = old.count();
@@ -101,13 +495,14 @@ impl Search {
             min_len + 1,
             &mut found,
         );
+        found.retain(|x| {
+            self.score(index, *x).is_some() && self.lengths.allows(&solution.words, &[*x])
+        });
         if found.is_empty() {
             solution.set_word(index, old);
             return false;
         } else {
-            found.sort_by_cached_key(|x| {
-                NotNan::new(-(x.scrabble_score() as f64 / x.count() as f64)).unwrap()
-            });
+            found.sort_by_cached_key(|x| NotNan::new(-self.score(index, *x).unwrap()).unwrap());
             let selected = iter::repeat(())
                 .take_while(|()| solution.rng.gen_bool(0.5))
                 .count()
@@ -128,6 +523,7 @@ impl Search {
     ) -> &[(LetterSet, LetterSet)] {
         self.access.fetch_add(1, Relaxed);
         &*self.cache[&(l1, l2, key, min)].get_or_init(|| {
+            self.counters.misses.fetch_add(1, Relaxed);
             let mut found = vec![];
             self.table
                 .binary
@@ -139,6 +535,9 @@ impl Search {
     }
     #[inline(never)]
     fn optimize2(&self, solution: &mut Solution, i1: usize, i2: usize, max_len: usize) -> bool {
+        if self.is_pinned(i1) || self.is_pinned(i2) {
+            return false;
+        }
         let old1 = solution.words[i1];
         let old2 = solution.words[i2];
         solution.set_word(i1, LetterSet::new());
@@ -156,6 +555,15 @@ impl Search {
             solution.remainder,
             old1.count() + old2.count() + 1,
         );
+        let (j1, j2) = if flipped { (i2, i1) } else { (i1, i2) };
+        let found = found
+            .iter()
+            .filter(|(w1, w2)| {
+                self.score(j1, *w1).is_some()
+                    && self.score(j2, *w2).is_some()
+                    && self.lengths.allows(&solution.words, &[*w1, *w2])
+            })
+            .collect::<Vec<_>>();
         if let Some(found) = found.choose(&mut solution.rng) {
             if found.0.count() <= max_len || found.1.count() <= max_len {
                 if flipped {
@@ -176,6 +584,85 @@ impl Search {
         solution.set_word(i2, old2);
         return false;
     }
+    /// Fills the slots at `indices[chosen.len()..]` with words from `remainder` whose lengths,
+    /// with the `chosen` ones, add up to at least `min_len`. Each slot but the last tries its
+    /// unary trie's candidates in random order; the last takes the smallest words that fit.
+    /// Gives up once `budget` candidates have been tried.
+    fn compose(
+        &self,
+        solution: &mut Solution,
+        indices: &[usize],
+        chosen: &mut Vec<LetterSet>,
+        min_len: usize,
+        budget: &mut usize,
+    ) -> bool {
+        let index = indices[chosen.len()];
+        let trie = &self.table.unary[self.source[index]];
+        let remainder = chosen.iter().fold(solution.remainder, |a, b| a - *b);
+        let mut found = vec![];
+        let last = chosen.len() + 1 == indices.len();
+        if last {
+            let placed = chosen.iter().map(|x| x.count()).sum::<usize>();
+            trie.search_smallest_subset(remainder, min_len.saturating_sub(placed), &mut found);
+        } else {
+            trie.search_all_subsets(remainder, &mut found);
+        }
+        found.retain(|x| {
+            self.score(index, *x).is_some() && {
+                chosen.push(*x);
+                let allowed = self.lengths.allows(&solution.words, chosen);
+                chosen.pop();
+                allowed
+            }
+        });
+        found.shuffle(&mut solution.rng);
+        if last {
+            if let Some(word) = found.first() {
+                chosen.push(*word);
+                return true;
+            }
+            return false;
+        }
+        for word in found {
+            if *budget == 0 {
+                return false;
+            }
+            *budget -= 1;
+            chosen.push(word);
+            if self.compose(solution, indices, chosen, min_len, budget) {
+                return true;
+            }
+            chosen.pop();
+        }
+        false
+    }
+    /// Replaces the words at `indices` together with longer ones, composing their unary tries
+    /// on demand. Like [Self::optimize2], but for any number of words.
+    #[inline(never)]
+    fn optimize_k(&self, solution: &mut Solution, indices: &[usize], max_len: usize) -> bool {
+        if indices.iter().any(|i| self.is_pinned(*i)) {
+            return false;
+        }
+        let old: Vec<LetterSet> = indices.iter().map(|i| solution.words[*i]).collect();
+        for i in indices {
+            solution.set_word(*i, LetterSet::new());
+        }
+        let min_len = old.iter().map(|x| x.count()).sum::<usize>() + 1;
+        let mut chosen = vec![];
+        let mut budget = COMPOSE_BUDGET;
+        if self.compose(solution, indices, &mut chosen, min_len, &mut budget)
+            && chosen.iter().any(|x| x.count() <= max_len)
+        {
+            for (i, word) in indices.iter().zip(chosen) {
+                solution.set_word(*i, word);
+            }
+            return true;
+        }
+        for (i, word) in indices.iter().zip(old) {
+            solution.set_word(*i, word);
+        }
+        false
+    }
     #[inline(never)]
     fn optimize(&self, solution: &mut Solution) {
         'outer: for max_len in 6.. {
@@ -185,6 +672,9 @@ impl Search {
                 let mut indices = (0..solution.words.len()).collect::<Vec<_>>();
                 indices.shuffle(&mut solution.rng);
                 for i in indices {
+                    if self.is_pinned(i) {
+                        continue;
+                    }
                     if solution.words[i].count() < max_len {
                         progress |= self.optimize1(solution, i);
                     } else {
@@ -209,6 +699,19 @@ impl Search {
                         }
                     }
                 }
+                // Three-word swaps are far costlier, so only a sample is tried once pairs
+                // stop helping.
+                let len = solution.words.len();
+                if !progress && len >= 3 && solution.remainder.count() > 0 {
+                    for _ in 0..TRIPLE_SAMPLES {
+                        let mut triple = index::sample(&mut solution.rng, len, 3).into_vec();
+                        triple.sort_unstable();
+                        if self.optimize_k(solution, &triple, max_len) {
+                            progress = true;
+                            break;
+                        }
+                    }
+                }
                 if !progress {
                     break;
                 }
@@ -217,8 +720,11 @@ impl Search {
     }
     pub fn anneal(&self, sol: &mut Solution) -> bool {
         for i in 0..10 {
+            if self.deadline.is_past() {
+                return false;
+            }
             self.optimize(sol);
-            if sol.is_done() {
+            if sol.is_done(&self.lengths) {
                 // println!("{}", self.format(sol));
                 return true;
             }
@@ -226,27 +732,159 @@ impl Search {
         }
         false
     }
+    /// Refits the unpinned answers of a solution an editor has changed by hand, so its
+    /// leftover letters are used up. Returns whether that worked.
+    pub fn repair(&self, solution: &mut Solution) -> bool {
+        self.is_done(solution) || self.anneal(solution)
+    }
+    /// Whether `solution` uses every quote letter within the length limits.
+    pub fn is_done(&self, solution: &Solution) -> bool {
+        solution.is_done(&self.lengths)
+    }
     pub fn solve(&self, seed: u64) -> Option<Solution> {
         let mut solution = self.start(seed)?;
-        if self.anneal(&mut solution) {
+        let done = self.anneal(&mut solution);
+        self.counters.seeds.fetch_add(1, Relaxed);
+        self.counters.best_remainder.fetch_min(solution.remainder.count(), Relaxed);
+        if done {
+            self.counters.solutions.fetch_add(1, Relaxed);
             return Some(solution);
         } else {
+            self.counters.failed.fetch_add(1, Relaxed);
+            for (letter, count) in solution.remainder.iter() {
+                if count > 0 {
+                    self.counters.leftover[letter].fetch_add(1, Relaxed);
+                }
+            }
             // println!("failed: {} {} {}", self.format(&solution), self.access.load(Relaxed), self.cache.len());
         }
         return None;
     }
-    pub fn get_words(&self, sol: &Solution) -> Vec<&FlatWord> {
-        let mut result = vec![];
-        'main: for (i, word) in sol.words.iter().enumerate() {
-            for w2 in &*self.table.dict {
-                if w2.letters == *word && w2.letter_vec.first() == Some(&self.source[i]) {
-                    result.push(w2);
-                    continue 'main;
+    /// Searches every combination of scored words with the right first letters, visiting at
+    /// most `budget` nodes.
+    pub fn solve_exact(&self, budget: usize) -> (ExactOutcome, Option<Solution>) {
+        let stop = |nodes| {
+            self.counters.exact_nodes.store(nodes, Relaxed);
+            self.deadline.is_past()
+        };
+        let outcome = self.exact_outcome(self.quote, budget, &stop);
+        let solution = match &outcome {
+            ExactOutcome::Found(words) => Some(Solution {
+                words: words.clone(),
+                remainder: LetterSet::new(),
+                rng: XorShiftRng::seed_from_u64(0),
+            })
+            .filter(|x| x.is_done(&self.lengths)),
+            _ => None,
+        };
+        (outcome, solution)
+    }
+    /// Runs the exact solver on `quote` in place of the puzzle's own, keeping the source,
+    /// scores and pinned answers.
+    pub fn exact_outcome(
+        &self,
+        quote: LetterSet,
+        budget: usize,
+        stop: &dyn Fn(usize) -> bool,
+    ) -> ExactOutcome {
+        // Only the open slots are searched, with the pinned answers already placed.
+        let pinned: Vec<LetterSet> = self.pinned.iter().flatten().map(|x| x.letters).collect();
+        let open: Vec<usize> = (0..self.source.len()).filter(|i| !self.is_pinned(*i)).collect();
+        let candidates = |first: Letter, remainder: LetterSet, placed: &[LetterSet]| {
+            let placed: Vec<LetterSet> = pinned.iter().chain(placed).cloned().collect();
+            let mut found = vec![];
+            self.table.unary[first].search_all_subsets(remainder, &mut found);
+            found.sort();
+            found.dedup();
+            // The same conditions as `Solution::is_done`, so a solution found is a valid one.
+            found.retain(|x| {
+                self.scores.contains_key(&(first, *x))
+                    && x.count() >= self.lengths.min_len
+                    && self.lengths.allows(&placed, &[*x])
+                    && !placed.contains(x)
+            });
+            found.sort_by_cached_key(|x| NotNan::new(-self.scores[&(first, *x)].0).unwrap());
+            found
+        };
+        let pinned_letters = pinned.iter().fold(LetterSet::new(), |a, b| a + *b);
+        if !pinned_letters.is_subset(quote) {
+            return ExactOutcome::Infeasible;
+        }
+        let source: Vec<Letter> = open.iter().map(|i| self.source[*i]).collect();
+        match ExactSolver::solve(quote - pinned_letters, &source, &candidates, budget, stop) {
+            ExactOutcome::Found(found) => {
+                let mut words: Vec<LetterSet> = self
+                    .pinned
+                    .iter()
+                    .map(|x| x.map_or(LetterSet::new(), |x| x.letters))
+                    .collect();
+                for (i, word) in open.iter().zip(found) {
+                    words[*i] = word;
                 }
+                ExactOutcome::Found(words)
             }
-            unreachable!();
+            outcome => outcome,
+        }
+    }
+    /// How many seeds have failed, and how many of those left each letter unused.
+    pub fn leftovers(&self) -> (usize, LetterMap<usize>) {
+        let failed = self.counters.failed.load(Relaxed);
+        let mut leftover = LetterMap::<usize>::new();
+        for (letter, count) in self.counters.leftover.iter() {
+            leftover[letter] = count.load(Relaxed);
+        }
+        (failed, leftover)
+    }
+    /// The distinct allowed answers starting with `first` that fit in the quote.
+    pub fn candidate_count(&self, first: Letter) -> usize {
+        let mut found = vec![];
+        self.table.unary[first].search_all_subsets(self.quote, &mut found);
+        found.sort();
+        found.dedup();
+        found
+            .iter()
+            .filter(|x| {
+                self.scores.contains_key(&(first, **x)) && x.count() >= self.lengths.min_len
+            })
+            .count()
+    }
+    pub fn quote(&self) -> LetterSet {
+        self.quote
+    }
+    pub fn source(&self) -> &[Letter] {
+        &self.source
+    }
+    pub fn progress(&self) -> SearchProgress {
+        let access = self.access.load(Relaxed);
+        let misses = self.counters.misses.load(Relaxed);
+        let best_remainder = self.counters.best_remainder.load(Relaxed);
+        SearchProgress {
+            elapsed: self.start_time.elapsed(),
+            exact_nodes: self.counters.exact_nodes.load(Relaxed),
+            seeds: self.counters.seeds.load(Relaxed),
+            solutions: self.counters.solutions.load(Relaxed),
+            best_remainder: (best_remainder != usize::MAX).then_some(best_remainder),
+            cache_hit_rate: if access == 0 {
+                0.0
+            } else {
+                1.0 - misses as f64 / access as f64
+            },
+        }
+    }
+    pub fn get_words(&self, sol: &Solution) -> Vec<&FlatWord> {
+        sol.words
+            .iter()
+            .enumerate()
+            .map(|(i, word)| self.word(i, *word).unwrap())
+            .collect()
+    }
+    /// The word used for `word` at `index`: the pinned one, or the best-scoring one with
+    /// those letters.
+    pub fn word(&self, index: usize, word: LetterSet) -> Option<&'static FlatWord> {
+        match self.pinned[index] {
+            Some(pinned) => Some(pinned),
+            None => Some(self.scores.get(&(self.source[index], word))?.1),
         }
-        result
     }
     pub fn format(&self, sol: &Solution) -> String {
         let mut result = String::new();
@@ -264,7 +902,7 @@ impl Search {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Solution {
     words: Vec<LetterSet>,
     remainder: LetterSet,
@@ -281,37 +919,56 @@ impl Solution {
     pub fn words(&self) -> &[LetterSet] {
         &self.words
     }
-    pub fn is_done(&self) -> bool {
+    /// The quote letters no answer uses yet.
+    pub fn remainder(&self) -> LetterSet {
+        self.remainder
+    }
+    pub fn is_done(&self, lengths: &LengthConstraints) -> bool {
         self.remainder.count() == 0
-            && self.words.iter().all(|x| x.count() > 1)
+            && lengths.is_satisfied(&self.words)
             && self.words.iter().collect::<HashSet<_>>().len() == self.words.len()
     }
 }
 
-pub async fn add_answers(pindex: usize) -> anyhow::Result<()> {
-    let mut puzzle = Puzzle::read(pindex, "stage1.json").await?;
-    let quote: LetterSet = puzzle
-        .quote_letters
-        .as_ref()
-        .unwrap()
-        .bytes()
-        .flat_map(|x| Letter::new(x))
-        .collect();
-    let source: Vec<_> = puzzle
-        .source_letters
-        .as_ref()
-        .unwrap()
-        .bytes()
-        .flat_map(|x| Letter::new(x))
-        .collect();
-    // println!("{:?}", source);
-    let search = Arc::new(Search::new(quote, source).await?);
-    let sol = stream::iter(0..1000)
+/// How many solutions to find per alternative wanted, before keeping the best-scoring ones.
+const SOLUTION_CANDIDATES: usize = 4;
+
+/// Prints a search's progress every interval until dropped.
+struct ProgressReporter(JoinHandle<()>);
+
+impl ProgressReporter {
+    fn start(search: &Arc<Search>, pindex: usize, interval: Duration) -> Self {
+        let search = search.clone();
+        ProgressReporter(tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            ticker.tick().await;
+            loop {
+                ticker.tick().await;
+                eprintln!("puzzle={} answers {}", pindex, search.progress());
+            }
+        }))
+    }
+}
+
+impl Drop for ProgressReporter {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+
+/// Anneals from a series of seeds until [SOLUTION_CANDIDATES] solutions per alternative are
+/// found, or the deadline passes, returning up to `count` of them, best-scoring first and then
+/// most diverse.
+async fn anneal(search: &Arc<Search>, pindex: usize, count: usize) -> Vec<(u64, Solution)> {
+    // `buffered` yields in seed order, so the chosen solutions do not depend on scheduling.
+    let mut candidates: Vec<(u64, Solution)> = stream::iter(0..MAX_SEEDS)
+        .take_while(|_| future::ready(!search.deadline.is_past()))
         .map(|seed| {
             let search = search.clone();
+            let seed = (pindex as u64) * MAX_SEEDS + seed;
             async move {
                 tokio::task::spawn_blocking(move || {
-                    stream::iter(search.solve((pindex as u64) * 1000 + seed))
+                    stream::iter(search.solve(seed).map(|sol| (seed, sol)))
                 })
                 .await
                 .unwrap()
@@ -319,17 +976,59 @@ pub async fn add_answers(pindex: usize) -> anyhow::Result<()> {
         })
         .buffered(num_cpus::get())
         .flatten()
-        .next()
-        .await
-        .ok_or(io::Error::new(ErrorKind::TimedOut, "timed out"))?;
-    let words = search.get_words(&sol);
-    let mut rng = XorShiftRng::seed_from_u64(pindex as u64);
+        .take(SOLUTION_CANDIDATES * count.max(1))
+        .collect()
+        .await;
+    let scored: Vec<(f64, &[LetterSet])> = candidates
+        .iter()
+        .map(|(_, sol)| (search.quality(sol), sol.words()))
+        .collect();
+    let chosen = most_diverse(&scored, count.max(1));
+    let mut candidates: Vec<_> = candidates.drain(..).map(Some).collect();
+    chosen.into_iter().map(|i| candidates[i].take().unwrap()).collect()
+}
+
+/// Picks up to `count` distinct answer sets from `(quality, answers)` pairs: the best first,
+/// then repeatedly the one sharing the fewest answers with any already picked. Quality breaks
+/// ties, then the earlier index.
+fn most_diverse(candidates: &[(f64, &[LetterSet])], count: usize) -> Vec<usize> {
+    let shared = |a: &[LetterSet], b: &[LetterSet]| a.iter().filter(|x| b.contains(x)).count();
+    let mut chosen: Vec<usize> = vec![];
+    while chosen.len() < count {
+        let next = candidates
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| !chosen.contains(i))
+            .filter_map(|(i, (quality, answers))| {
+                let overlap = chosen.iter().map(|j| shared(answers, candidates[*j].1)).max();
+                match overlap {
+                    // Every answer already picked: the same set, found from another seed.
+                    Some(overlap) if overlap == answers.len() => None,
+                    overlap => Some((i, overlap.unwrap_or(0), *quality)),
+                }
+            })
+            .min_by_key(|(i, overlap, quality)| (*overlap, NotNan::new(-quality).unwrap(), *i));
+        match next {
+            Some((i, _, _)) => chosen.push(i),
+            None => break,
+        }
+    }
+    chosen
+}
+
+/// Assigns each answer letter a quote cell holding the same letter, shuffled with a seed so
+/// reruns place answers identically. `words` are in source order, as [Search::get_words]
+/// returns them, and each clue stays at its word's position.
+pub fn to_clues(
+    words: &[&FlatWord],
+    quote_letters: &str,
+    source_letters: &str,
+    seed: u64,
+) -> Vec<Clue> {
+    let mut rng = XorShiftRng::seed_from_u64(seed);
     let mut positions: LetterMap<Vec<usize>> = Letter::all()
         .map(|l| {
-            let mut result: Vec<_> = puzzle
-                .quote_letters
-                .as_ref()
-                .unwrap()
+            let mut result: Vec<_> = quote_letters
                 .chars()
                 .positions(|l2| l.to_char() == l2)
                 .collect();
@@ -337,39 +1036,185 @@ pub async fn add_answers(pindex: usize) -> anyhow::Result<()> {
             result
         })
         .collect();
-    let clues: Vec<Clue> = words
+    assert_eq!(words.len(), source_letters.len(), "one answer per source letter");
+    words
         .iter()
-        .map(|w| Clue {
-            clue: None,
-            answer: w.word.to_string(),
-            answer_letters: w.letter_vec.iter().join(""),
-            indices: w
-                .letter_vec
-                .iter()
-                .map(|l| positions[*l].pop().unwrap())
-                .collect(),
+        .zip(source_letters.bytes())
+        .map(|(w, first)| {
+            assert_eq!(w.letter_vec.first(), Letter::new(first).ok().as_ref(), "{}", w.word);
+            Clue {
+                clue: None,
+                answer: w.word.to_string(),
+                answer_letters: w.letter_vec.iter().join(""),
+                indices: w
+                    .letter_vec
+                    .iter()
+                    .map(|l| positions[*l].pop().unwrap())
+                    .collect(),
+                source: None,
+                breaks: Some(w.breaks.iter().map(|x| *x as usize).collect())
+                    .filter(|x: &Vec<usize>| !x.is_empty()),
+            }
         })
+        .collect()
+}
+
+/// Why no solution was found: interrupted, out of time, or out of `budget`.
+fn no_solution(deadline: Deadline, budget: &str) -> io::Error {
+    if is_interrupted() {
+        io::Error::new(ErrorKind::Interrupted, "interrupted")
+    } else if deadline.is_past() {
+        io::Error::new(ErrorKind::TimedOut, "time limit reached")
+    } else {
+        io::Error::new(ErrorKind::TimedOut, budget)
+    }
+}
+
+/// Why the exact search found no answers at all. The quote is only rejected when nothing but
+/// the trie table itself limited the vocabulary; otherwise looser options might still work.
+fn infeasible(
+    search: &Search,
+    options: &SearchOptions,
+    answers: &AnswerConstraints,
+) -> anyhow::Error {
+    let manifest = &search.table.selection.manifest;
+    let lengths = &options.lengths;
+    let rank = max_rank(options.difficulty).min(manifest.max_rank);
+    let mut limits = vec![format!("{} difficulty", options.difficulty)];
+    if lengths.min_len > manifest.min_letters {
+        limits.push(format!("at least {} letters", lengths.min_len));
+    }
+    if lengths.max_len != usize::MAX {
+        limits.push(format!("at most {} letters", lengths.max_len));
+    }
+    if lengths.max_long != usize::MAX {
+        limits.push(format!("at most {} over {} letters", lengths.max_long, lengths.long_len));
+    }
+    if !answers.exclude.is_empty() {
+        limits.push(format!("{} words excluded", answers.exclude.len()));
+    }
+    if !answers.pin.is_empty() {
+        limits.push(format!("{} answers pinned", answers.pin.len()));
+    }
+    let reason = format!(
+        "no set of answers from the {} most frequent words of {}+ letters ({}) uses exactly the \
+         quote letters",
+        rank,
+        manifest.min_letters,
+        limits.join(", ")
+    );
+    if rank < manifest.max_rank || limits.len() > 1 {
+        anyhow!(reason)
+    } else {
+        QuoteRejected(reason).into()
+    }
+}
+
+/// Runs the solvers `options` asks for, returning up to `options.alternatives` solutions.
+/// Prints progress every `options.progress_interval` meanwhile.
+async fn find_solutions(
+    search: &Arc<Search>,
+    pindex: usize,
+    options: &SearchOptions,
+    deadline: Deadline,
+    answers: &AnswerConstraints,
+) -> anyhow::Result<Vec<(Option<u64>, Solution)>> {
+    let _reporter = options
+        .progress_interval
+        .map(|interval| ProgressReporter::start(search, pindex, interval));
+    let exact = match options.solver {
+        SolverMode::Anneal => None,
+        SolverMode::Exact | SolverMode::Auto => {
+            let search = search.clone();
+            let budget = options.exact_budget;
+            Some(tokio::task::spawn_blocking(move || search.solve_exact(budget)).await?)
+        }
+    };
+    let found = match exact {
+        Some((ExactOutcome::Infeasible, _)) => return Err(infeasible(search, options, answers)),
+        Some((_, Some(sol))) if options.solver == SolverMode::Exact => vec![(None, sol)],
+        Some((_, None)) if options.solver == SolverMode::Exact => {
+            return Err(no_solution(deadline, "exact search ran out of budget").into());
+        }
+        exact => {
+            let annealed = anneal(search, pindex, options.alternatives).await;
+            if annealed.is_empty() {
+                let sol = exact
+                    .and_then(|(_, sol)| sol)
+                    .ok_or_else(|| no_solution(deadline, "no solution from any seed"))?;
+                vec![(None, sol)]
+            } else {
+                annealed.into_iter().map(|(seed, sol)| (Some(seed), sol)).collect()
+            }
+        }
+    };
+    Ok(found)
+}
+
+pub async fn add_answers(pindex: usize, options: &SearchOptions) -> anyhow::Result<()> {
+    let deadline = Deadline::after(options.time_limit);
+    let mut puzzle = Puzzle::read(pindex, Stage::Letters).await?;
+    let quote: LetterSet = puzzle
+        .quote_letters
+        .as_ref()
+        .unwrap()
+        .bytes()
+        .flat_map(|x| Letter::new(x))
         .collect();
-    let mut clues2 = LetterMap::<Vec<Clue>>::new();
-    for clue in clues {
-        clues2[Letter::new(clue.answer_letters.bytes().next().expect("first letter"))
-            .expect("ascii")]
-        .push(clue);
-    }
-    // println!("{:?}", clues2);
-    // println!("{:?}", puzzle.source_letters);
-    let clues3 = puzzle
+    let source: Vec<_> = puzzle
         .source_letters
         .as_ref()
         .unwrap()
         .bytes()
-        .map(|x| {
-            let x = Letter::new(x).unwrap();
-            clues2[x].pop().unwrap_or_else(|| panic!("Missing {:?}", x))
+        .flat_map(|x| Letter::new(x))
+        .collect();
+    // println!("{:?}", source);
+    let table = FLAT_TRIE_TABLE.get().await.clone_error_static()?;
+    options.lengths.check_mean(quote.count(), source.len())?;
+    let scorer = default_scorer(table.words(), options.difficulty).await?;
+    let mut answers = AnswerConstraints::load(pindex).await?;
+    answers.extend(&options.answers);
+    let lengths = options.lengths.clone();
+    let search = Arc::new(Search::new(quote, source, &*scorer, lengths, &answers, deadline).await?);
+    let found = match find_solutions(&search, pindex, options, deadline, &answers).await {
+        Ok(found) => found,
+        Err(error) => {
+            if !is_interrupted() {
+                let budget = options.exact_budget;
+                explain_failure(&search, &puzzle.quote, pindex, budget, deadline).await?;
+            }
+            return Err(error);
+        }
+    };
+    Diagnosis::remove(pindex).await?;
+    let familiarity = Familiarity::new(table.words());
+    let alternatives: Vec<Alternative> = found
+        .iter()
+        .map(|(seed, sol)| {
+            let words = search.get_words(sol);
+            Alternative {
+                clues: to_clues(
+                    &words,
+                    puzzle.quote_letters.as_ref().unwrap(),
+                    puzzle.source_letters.as_ref().unwrap(),
+                    pindex as u64,
+                ),
+                quality: search.quality(sol),
+                search_seed: *seed,
+                difficulty: Some(Difficulty {
+                    target: options.difficulty,
+                    score: difficulty_score(&words, &familiarity),
+                }),
+            }
         })
         .collect();
-    puzzle.clues = Some(clues3);
-    puzzle.write(pindex, "stage2.json").await?;
+    puzzle.use_alternative(&alternatives[0]);
+    puzzle.alternatives = (options.alternatives > 1).then_some(alternatives);
+    let hashes = ARTIFACT_HASHES.get().await.clone_error_static()?;
+    let provenance = puzzle.provenance_mut();
+    provenance.dict_hash = Some(hashes.dict.clone());
+    provenance.trie_hash = Some(hashes.trie.clone());
+    puzzle.write(pindex, Stage::Answers).await?;
     Ok(())
 }
 
@@ -393,3 +1238,108 @@ async fn test_search() -> anyhow::Result<()> {
     println!("{:?}", start.elapsed());
     Ok(())
 }
+
+#[tokio::test]
+async fn test_to_clues() -> anyhow::Result<()> {
+    use crate::trie_table::fixture;
+    let table = fixture(&["tone", "toad", "ant"]).await?;
+    let word = |x: &str| table.dict.iter().find(|w| w.word == x).unwrap();
+    let answers = |clues: Vec<Clue>| clues.into_iter().map(|x| x.answer).collect::<Vec<_>>();
+    let quote = "TONETOADANT";
+    let clues = to_clues(&[word("tone"), word("toad"), word("ant")], quote, "TTA", 1);
+    assert_eq!(answers(clues.clone()), vec!["tone", "toad", "ant"]);
+    let mut cells: Vec<usize> = clues.iter().flat_map(|x| x.indices.clone()).collect();
+    cells.sort();
+    assert_eq!(cells, (0..quote.len()).collect::<Vec<_>>());
+    let clues = to_clues(&[word("toad"), word("tone"), word("ant")], quote, "TTA", 1);
+    assert_eq!(answers(clues), vec!["toad", "tone", "ant"]);
+    Ok(())
+}
+
+#[test]
+fn test_length_constraints() {
+    let lengths = LengthConstraints {
+        min_len: 3,
+        max_len: 12,
+        target_mean: Some(7.0),
+        long_len: 9,
+        max_long: 1,
+    };
+    let word = |n| LetterSet::from_str(&"A".repeat(n));
+    let placed = [word(10), LetterSet::new(), word(6)];
+    assert!(lengths.allows(&placed, &[word(8)]));
+    assert!(!lengths.allows(&placed, &[word(10)]));
+    assert!(!lengths.allows(&[word(6), LetterSet::new()], &[word(13)]));
+    assert!(lengths.is_satisfied(&[word(10), word(3), word(6)]));
+    assert!(!lengths.is_satisfied(&[word(10), word(2), word(6)]));
+    assert!(!lengths.is_satisfied(&[word(10), word(11), word(6)]));
+    assert!(lengths.check_mean(150, 20).is_ok());
+    assert!(lengths.check_mean(150, 30).is_err());
+    assert!(LengthConstraints::default().check_mean(150, 30).is_ok());
+}
+
+#[test]
+fn test_most_diverse() {
+    let words = |x: &str| x.split(' ').map(LetterSet::from_str).collect::<Vec<_>>();
+    let a = words("tea ant not");
+    let b = words("tea ant ten");
+    let c = words("toe oat net");
+    let candidates = [(2.0, &a[..]), (3.0, &b[..]), (1.0, &c[..]), (2.5, &b[..])];
+    assert_eq!(most_diverse(&candidates, 1), vec![1]);
+    // `c` shares nothing with `b`; the copy of `b` is never picked.
+    assert_eq!(most_diverse(&candidates, 4), vec![1, 2, 0]);
+}
+
+#[test]
+fn test_answer_constraints() {
+    let mut answers = AnswerConstraints::from_toml(
+        r#"
+        exclude = ["Moist"]
+
+        [[pin]]
+        position = 0
+        answer = "shakespeare"
+
+        [[pin]]
+        position = 3
+        answer = "hamlet"
+        "#,
+    )
+    .unwrap();
+    assert!(answers.is_excluded("moist"));
+    assert!(!answers.is_excluded("most"));
+    let flags = AnswerConstraints {
+        pin: vec!["3 = othello".parse().unwrap()],
+        exclude: vec!["damp".to_string()],
+    };
+    answers.extend(&flags);
+    assert_eq!(
+        answers.pin,
+        vec![
+            PinnedAnswer { position: 0, answer: "shakespeare".to_string() },
+            PinnedAnswer { position: 3, answer: "othello".to_string() },
+        ]
+    );
+    assert!(answers.is_excluded("damp"));
+    assert!("othello".parse::<PinnedAnswer>().is_err());
+    assert!("x=othello".parse::<PinnedAnswer>().is_err());
+    assert!(AnswerConstraints::from_toml("pinned = []").is_err());
+}
+
+#[test]
+fn test_search_progress() {
+    assert!(!Deadline::after(None).is_past());
+    assert!(Deadline::after(Some(Duration::ZERO)).is_past());
+    let progress = SearchProgress {
+        elapsed: Duration::from_secs(12),
+        exact_nodes: 5000,
+        seeds: 40,
+        solutions: 1,
+        best_remainder: Some(3),
+        cache_hit_rate: 0.9,
+    };
+    assert_eq!(
+        progress.to_string(),
+        "elapsed=12s exact_nodes=5000 seeds=40 solutions=1 best_remainder=3 cache_hits=90%"
+    );
+}
@@ -12,14 +12,24 @@ use tokio::fs;
 use tokio::fs::{create_dir, create_dir_all, read_dir};
 use tokio::task::JoinHandle;
 
-use crate::{PACKAGE_PATH, write_path};
+use crate::pack::read_packs;
+use crate::{PACKAGE_PATH, PUZZLES_PATH, write_path};
 
 #[derive(Serialize, Deserialize)]
 struct PuzzleIndex {
     links: Vec<PuzzleLink>,
+    packs: Vec<PackLinks>,
 }
 
-#[derive(Serialize, Deserialize, Ord, PartialOrd, Eq, PartialEq)]
+#[derive(Serialize, Deserialize)]
+struct PackLinks {
+    name: String,
+    title: String,
+    description: String,
+    links: Vec<PuzzleLink>,
+}
+
+#[derive(Serialize, Deserialize, Ord, PartialOrd, Eq, PartialEq, Clone)]
 struct PuzzleLink {
     index: usize,
     name: String,
@@ -27,7 +37,10 @@ struct PuzzleLink {
 }
 
 async fn build_index() -> io::Result<()> {
-    let mut index = PuzzleIndex { links: vec![] };
+    let mut index = PuzzleIndex {
+        links: vec![],
+        packs: vec![],
+    };
     let mut f = vec![];
     let mut f = &mut f;
     writeln!(f, "<!DOCTYPE html>")?;
@@ -54,6 +67,19 @@ async fn build_index() -> io::Result<()> {
         )?;
     }
     index.links.sort();
+    for (name, pack) in read_packs().await? {
+        let links = pack
+            .puzzles
+            .iter()
+            .filter_map(|p| index.links.iter().find(|x| x.index == *p).cloned())
+            .collect();
+        index.packs.push(PackLinks {
+            name,
+            title: pack.title,
+            description: pack.description,
+            links,
+        });
+    }
     writeln!(f, "<p><a href=\"./ACKNOWLEDGEMENTS.txt\">ACKNOWLEDGEMENTS</a></p>")?;
     writeln!(f, "<p><a href=\"./LICENSE.txt\">LICENSE</a></p>")?;
     writeln!(f, "</body>")?;
@@ -90,7 +116,7 @@ pub fn copy_dir<'a>(source: &'a Path, dest: &'a Path) -> BoxFuture<'a, io::Resul
 }
 
 pub async fn copy_puzzles() -> io::Result<()> {
-    let mut dir = read_dir(PACKAGE_PATH.join("puzzles")).await?;
+    let mut dir = read_dir(&*PUZZLES_PATH).await?;
     let output = PACKAGE_PATH.join("build/site/puzzles");
     create_dir_all(&output).await?;
     while let Some(entry) = dir.next_entry().await? {
@@ -1,27 +1,144 @@
-use std::{io, mem};
+use std::{io, mem, slice};
 use std::alloc::Allocator;
 use std::borrow::Borrow;
 use std::cmp::Ordering;
 use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fmt::{Debug, Formatter};
 use std::mem::size_of;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::time::Instant;
 use futures::stream::FuturesUnordered;
-use futures::{StreamExt, TryStreamExt};
+use futures::{stream, StreamExt, TryStreamExt};
 use rand::SeedableRng;
 
 use rand::seq::SliceRandom;
 use rand_xorshift::XorShiftRng;
+use tokio::io::{AsyncReadExt, BufWriter};
 use tokio::sync::Semaphore;
 use acrostic_core::letter::{Letter, LetterMap, LetterSet};
+use anyhow::anyhow;
+use itertools::Itertools;
+use sha2::{Digest, Sha256};
 
 use crate::dict::{FLAT_WORDS, FlatWord};
-use crate::PACKAGE_PATH;
-use crate::util::alloc::{MmapAllocator, restore_vec, save_vec};
+use crate::dict_manifest::{DictSelection, DICT_SELECTION};
+use crate::trie_bundle::{TrieBundle, TrieBundleWriter, BUNDLE_FILE};
+use crate::{read_path_to_string, write_path, PACKAGE_PATH};
 use crate::util::lazy_async::CloneError;
 use crate::util::parallel::Parallelism;
 
+/// Starts every trie file.
+const TRIE_MAGIC: [u8; 8] = *b"ACROTRIE";
+
+/// Bumped whenever the layout of [FlatTrieEntry] or the trie encoding changes.
+const TRIE_VERSION: u32 = 2;
+
+/// Written in native byte order, so files from a machine of the other endianness are caught.
+const ENDIAN_MARK: u32 = 0x0102_0304;
+
+/// The header's size, which keeps the entries after it aligned.
+const TRIE_HEADER_LEN: usize = 64;
+
+/// What a trie file records about its entries: the magic number and endianness mark, then
+/// the format version, the size of one entry, how many entries there are and the SHA-256 of
+/// their bytes.
+#[derive(Debug, Clone, Eq, PartialEq)]
+struct TrieHeader {
+    version: u32,
+    entry_size: u32,
+    count: u64,
+    hash: [u8; 32],
+}
+
+fn entry_bytes<V>(entries: &[FlatTrieEntry<V>]) -> &[u8] {
+    unsafe { slice::from_raw_parts(entries.as_ptr() as *const u8, mem::size_of_val(entries)) }
+}
+
+impl TrieHeader {
+    fn new<V>(entries: &[FlatTrieEntry<V>]) -> Self {
+        TrieHeader {
+            version: TRIE_VERSION,
+            entry_size: size_of::<FlatTrieEntry<V>>() as u32,
+            count: entries.len() as u64,
+            hash: Sha256::digest(entry_bytes(entries)).into(),
+        }
+    }
+    fn to_bytes(&self) -> [u8; TRIE_HEADER_LEN] {
+        let mut bytes = [0u8; TRIE_HEADER_LEN];
+        bytes[0..8].copy_from_slice(&TRIE_MAGIC);
+        bytes[8..12].copy_from_slice(&ENDIAN_MARK.to_ne_bytes());
+        bytes[12..16].copy_from_slice(&self.version.to_ne_bytes());
+        bytes[16..20].copy_from_slice(&self.entry_size.to_ne_bytes());
+        bytes[24..32].copy_from_slice(&self.count.to_ne_bytes());
+        bytes[32..64].copy_from_slice(&self.hash);
+        bytes
+    }
+    fn parse(bytes: &[u8; TRIE_HEADER_LEN]) -> Result<Self, String> {
+        let u32_at = |i: usize| u32::from_ne_bytes(bytes[i..i + 4].try_into().unwrap());
+        if bytes[0..8] != TRIE_MAGIC {
+            return Err("not a trie file, or one from before trie files had headers".to_string());
+        }
+        if u32_at(8) != ENDIAN_MARK {
+            return Err("written on a machine of the other endianness".to_string());
+        }
+        Ok(TrieHeader {
+            version: u32_at(12),
+            entry_size: u32_at(16),
+            count: u64::from_ne_bytes(bytes[24..32].try_into().unwrap()),
+            hash: bytes[32..64].try_into().unwrap(),
+        })
+    }
+    /// Whether the header is for this format version and `len` bytes, header included.
+    fn check_len(&self, len: u64) -> Result<(), String> {
+        if self.version != TRIE_VERSION {
+            return Err(format!(
+                "format version {}, but this build reads version {}",
+                self.version, TRIE_VERSION
+            ));
+        }
+        let expected = TRIE_HEADER_LEN as u64 + self.count * self.entry_size as u64;
+        if len != expected {
+            return Err(format!(
+                "{} bytes, but a header promising {} entries needs {}; it may be truncated",
+                len, self.count, expected
+            ));
+        }
+        Ok(())
+    }
+    /// Whether the entries have the layout of this build's entries of type `V`.
+    fn check_entries<V>(&self) -> Result<(), String> {
+        let entry_size = size_of::<FlatTrieEntry<V>>() as u32;
+        if self.entry_size != entry_size {
+            return Err(format!(
+                "entries of {} bytes, but this build's are {} bytes",
+                self.entry_size, entry_size
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Checks the header and length of a trie as written by [FlatTrie::to_bytes], without
+/// knowing its entry type.
+fn check_trie_header(bytes: &[u8]) -> Result<TrieHeader, String> {
+    let Some(header) = bytes.first_chunk::<TRIE_HEADER_LEN>() else {
+        return Err(format!("{} bytes is too short for a trie header", bytes.len()));
+    };
+    let header = TrieHeader::parse(header)?;
+    header.check_len(bytes.len() as u64)?;
+    Ok(header)
+}
+
+/// Like [check_trie_header], and also checks the entries against the header's hash. This
+/// reads every byte, so a trie table checks each of its tries once, when it is loaded.
+pub fn check_trie_bytes(bytes: &[u8]) -> Result<(), String> {
+    let header = check_trie_header(bytes)?;
+    if Sha256::digest(&bytes[TRIE_HEADER_LEN..]).as_slice() != header.hash {
+        return Err("the entries do not match the header's hash".to_string());
+    }
+    Ok(())
+}
+
 #[repr(C)]
 pub enum FlatTrieEntry<V> {
     Leaf { key: LetterSet, value: V },
@@ -57,10 +174,27 @@ impl<V: Clone> FlatTrie<V> {
             result
         }
     }
-    pub async unsafe fn restore(filename: &Path) -> io::Result<Box<Self, MmapAllocator>>
-    {
-        let vec: Box<[FlatTrieEntry<V>], MmapAllocator> = restore_vec(filename).await?;
-        Ok(Self::new_unchecked_box(vec))
+    /// The entries after a [TrieHeader], as [Self::from_bytes] reads them.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = TrieHeader::new(&self.0).to_bytes().to_vec();
+        bytes.extend_from_slice(entry_bytes(&self.0));
+        bytes
+    }
+    /// Views bytes from [Self::to_bytes] as a trie, checking their header and length but not
+    /// their hash (see [check_trie_bytes]). The bytes must be aligned for the entries.
+    pub fn from_bytes(bytes: &[u8]) -> Result<&Self, String> {
+        check_trie_header(bytes)?.check_entries::<V>()?;
+        let entries = &bytes[TRIE_HEADER_LEN..];
+        if !(entries.as_ptr() as usize).is_multiple_of(mem::align_of::<FlatTrieEntry<V>>()) {
+            return Err("the entries are not aligned".to_string());
+        }
+        let entries = unsafe {
+            slice::from_raw_parts(
+                entries.as_ptr() as *const FlatTrieEntry<V>,
+                entries.len() / size_of::<FlatTrieEntry<V>>(),
+            )
+        };
+        Ok(Self::new_unchecked_ref(entries))
     }
     pub fn new_unchecked_ref(b: &[FlatTrieEntry<V>]) -> &Self { unsafe { mem::transmute(b) } }
     pub fn new_unchecked_mut(b: &mut [FlatTrieEntry<V>]) -> &mut Self {
@@ -147,6 +281,34 @@ impl<V: Clone> FlatTrie<V> {
             }
         }
     }
+    /// Every value whose key is a subset of `superset`.
+    pub fn search_all_subsets(&self, superset: LetterSet, found: &mut Vec<V>) {
+        match self.view() {
+            FlatTrieView::Empty => {}
+            FlatTrieView::Leaf {
+                key,
+                value,
+                remainder,
+            } => {
+                if key.is_subset(superset) {
+                    found.push(value.clone());
+                }
+                remainder.search_all_subsets(superset, found)
+            }
+            FlatTrieView::Node {
+                letter,
+                without,
+                with,
+            } => {
+                without.search_all_subsets(superset, found);
+                if superset[letter] > 0 {
+                    let mut superset2 = superset;
+                    superset2[letter] -= 1;
+                    with.search_all_subsets(superset2, found);
+                }
+            }
+        }
+    }
     #[inline(never)]
     pub fn search_smallest_subset(&self, key: LetterSet, min_len: usize, result: &mut Vec<V>) {
         for len in min_len..=key.count() {
@@ -277,92 +439,175 @@ impl<V: Debug> Debug for FlatTrieEntry<V> {
     }
 }
 
-pub async fn build_trie() -> anyhow::Result<()> {
-    let dict = FLAT_WORDS.get_static().await?;
-    let mut binary = BTreeMap::<(Letter, Letter), Vec<(LetterSet, (LetterSet, LetterSet))>>::new();
-    let mut unary = BTreeMap::<Letter, Vec<(LetterSet, LetterSet)>>::new();
-    for l in Letter::all() {
-        unary.insert(l, vec![]);
-    }
-    for l1 in Letter::all() {
-        for l2 in Letter::all() {
-            binary.insert((l1, l2), vec![]);
-        }
+/// The bundle section holding the unary trie for words starting with `l`.
+pub fn unary_section(l: Letter) -> String {
+    format!("unary/{}", l)
+}
+
+/// The bundle section holding the binary trie for a pair of first letters in order.
+pub fn binary_section(l1: Letter, l2: Letter) -> String {
+    format!("binary/{}{}", l1, l2)
+}
+
+/// Every section of a trie table's bundle, with the first letters each depends on.
+pub fn sections() -> Vec<(String, Vec<Letter>)> {
+    let unary = Letter::all().map(|l| (unary_section(l), vec![l]));
+    let binary = Letter::all()
+        .combinations_with_replacement(2)
+        .map(|ls| (binary_section(ls[0], ls[1]), ls));
+    unary.chain(binary).collect()
+}
+
+/// A unary trie over the letters of `words1`, or a binary trie over every pair from `words1`
+/// and `words2`, ready for a bundle.
+pub fn section_bytes(words1: &[LetterSet], words2: Option<&[LetterSet]>) -> Vec<u8> {
+    match words2 {
+        None => words1.iter().map(|x| (*x, *x))
+            .collect::<Box<FlatTrie<LetterSet>>>()
+            .to_bytes(),
+        Some(words2) => words1.iter()
+            .flat_map(|w1| words2.iter().map(move |w2| (*w1 + *w2, (*w1, *w2))))
+            .collect::<Box<FlatTrie<(LetterSet, LetterSet)>>>()
+            .to_bytes(),
     }
-    let mut words = vec![];
-    for word in dict.iter() {
-        if word.letters.count() > 5 {
-            words.push(word);
+}
+
+/// Written last when building a trie table: a hash of the words starting with each letter.
+/// Tables agreeing on a letter's words can share the tries that only involve such letters.
+pub const WORD_SETS_FILE: &str = "word_sets.json";
+
+pub type WordSets = BTreeMap<String, String>;
+
+fn word_sets(by_letter: &LetterMap<Vec<&FlatWord>>) -> WordSets {
+    by_letter
+        .iter()
+        .map(|(l, words)| {
+            // Tries from another format version are never reused.
+            let mut hasher = Sha256::new();
+            hasher.update(TRIE_VERSION.to_le_bytes());
+            for word in words {
+                hasher.update(word.word.as_bytes());
+                hasher.update(b"\n");
+            }
+            (l.to_string(), hex::encode(&hasher.finalize()[..8]))
+        })
+        .collect()
+}
+
+/// The words `selection` takes from `dict`, by first letter.
+fn words_by_letter<'a>(
+    selection: &DictSelection,
+    dict: &'a [FlatWord],
+) -> LetterMap<Vec<&'a FlatWord>> {
+    let mut by_letter = LetterMap::<Vec<&FlatWord>>::new();
+    for word in selection.words(dict) {
+        if let Some(first) = word.letter_vec.first() {
+            by_letter[*first].push(word);
         }
     }
-    let words = &words[0..15000];
-    for word1 in words {
-        if let Some(first1) = word1.letter_vec.first() {
-            unary
-                .entry(*first1)
-                .or_default()
-                .push((word1.letters, word1.letters));
-            for word2 in words {
-                if let Some(first2) = word2.letter_vec.first() {
-                    if first1 <= first2 {
-                        binary.entry((*first1, *first2)).or_default().push((
-                            word1.letters + word2.letters,
-                            (word1.letters, word2.letters),
-                        ));
-                    }
-                }
-            }
+    by_letter
+}
+
+/// The word sets of the table `selection` builds from `dict`, as written to [WORD_SETS_FILE].
+pub fn selected_word_sets(selection: &DictSelection, dict: &[FlatWord]) -> WordSets {
+    word_sets(&words_by_letter(selection, dict))
+}
+
+/// Every trie table under `build/tries/` that finished building, with its word sets. Tables
+/// whose bundles cannot be opened, such as ones from older builds, are skipped.
+async fn completed_tables() -> anyhow::Result<Vec<(TrieBundle, WordSets)>> {
+    let mut tables = vec![];
+    let mut entries = match tokio::fs::read_dir(PACKAGE_PATH.join("build/tries")).await {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(tables),
+        Err(e) => return Err(e.into()),
+    };
+    while let Some(entry) = entries.next_entry().await? {
+        let sets = match read_path_to_string(&entry.path().join(WORD_SETS_FILE)).await {
+            Ok(contents) => serde_json::from_str(&contents)?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(e.into()),
+        };
+        match TrieBundle::open(&entry.path().join(BUNDLE_FILE)).await {
+            Ok(bundle) => tables.push((bundle, sets)),
+            Err(e) => eprintln!("not reusing tries from {:?}: {}", entry.path(), e),
         }
     }
+    Ok(tables)
+}
 
-    for (l1, vec) in unary {
-        println!("{:?}", l1);
-        save_vec::<FlatTrieEntry<LetterSet>>(
-            &PACKAGE_PATH.join(&format!("build/unary/map_{}.dat", l1)),
-            vec.into_iter()
-                .collect::<Box<FlatTrie<LetterSet>>>()
-                .as_slice(),
-        ).await?;
+/// The section from a completed table whose words starting with `letters` are the same.
+fn reusable<'a>(
+    tables: &'a [(TrieBundle, WordSets)],
+    sets: &WordSets,
+    letters: &[Letter],
+    name: &str,
+) -> Option<&'a [u8]> {
+    let matches = |other: &WordSets| {
+        letters.iter().all(|l| other.get(&l.to_string()) == sets.get(&l.to_string()))
+    };
+    tables
+        .iter()
+        .filter(|(_, other)| matches(other))
+        .find_map(|(bundle, _)| bundle.section(name).ok())
+}
+
+/// Builds the trie table for the dictionary manifest into one bundle file, copying the tries
+/// of earlier tables wherever the words they cover are unchanged.
+pub async fn build_trie() -> anyhow::Result<()> {
+    let dict = FLAT_WORDS.get_static().await?;
+    let selection = DICT_SELECTION.get().await.clone_error_static()?;
+    let missing = selection.missing_allowed(dict);
+    if !missing.is_empty() {
+        return Err(anyhow!("allowed words are not in the dictionary: {}", missing.join(", ")));
+    }
+    let by_letter = words_by_letter(selection, dict);
+    let sets = word_sets(&by_letter);
+    let by_letter = by_letter.map(|x| x.iter().map(|x| x.letters).collect::<Vec<_>>());
+    let dir = selection.dir();
+    tokio::fs::create_dir_all(&dir).await?;
+    // Opened before anything is replaced, so a rebuild can reuse its own table's tries.
+    let tables = completed_tables().await?;
+    match tokio::fs::remove_file(dir.join(WORD_SETS_FILE)).await {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e.into()),
+        _ => {}
     }
+
+    let sections = sections();
+    let partial = dir.join(format!("{}.partial", BUNDLE_FILE));
+    let file = tokio::fs::File::create(&partial).await?;
+    let mut writer = TrieBundleWriter::new(BufWriter::new(file), sections.len()).await?;
     let parallelism = Parallelism::new();
-    binary.into_iter()
-        .map(|((l1, l2), vec)| {
-            let parallelism = &parallelism;
-            async move {
-                let built: Box<FlatTrie<(LetterSet, LetterSet)>> = parallelism.run_blocking(move || {
-                    println!("Computing {:?}/{:?}", l1, l2);
-                    vec.into_iter()
-                        .collect::<Box<FlatTrie<(LetterSet, LetterSet)>>>()
-                }).await;
-                save_vec::<FlatTrieEntry<(LetterSet, LetterSet)>>(
-                    &PACKAGE_PATH.join(&format!("build/binary/map_{}_{}.dat", l1, l2)),
-                    built.as_slice()).await?;
-                println!("Done {:?}/{:?}", l1, l2);
-                Result::<(), io::Error>::Ok(())
+    let by_letter = &by_letter;
+    let (tables, sets, parallelism) = (&tables, &sets, &parallelism);
+    let mut built = stream::iter(sections)
+        .map(|(name, letters)| async move {
+            if let Some(bytes) = reusable(tables, sets, &letters, &name) {
+                return (name, bytes.to_vec());
             }
+            let words1 = by_letter[letters[0]].clone();
+            let words2 = letters.get(1).map(|l| by_letter[*l].clone());
+            let bytes = parallelism
+                .run_blocking(move || section_bytes(&words1, words2.as_deref()))
+                .await;
+            println!("Computed {}", name);
+            (name, bytes)
         })
-        .collect::<FuturesUnordered<_>>()
-        .try_collect::<()>().await?;
-    // let mut tasks = vec![];
-    // let semaphore = Semaphore::new(num_cpus::get());
-    // for ((l1, l2), vec) in binary {
-    //     tasks.push(tokio::task::spawn(|| {
-    //         {
-    //             let guard = semaphore.acquire();
-    //             println!("{:?}/{:?}", l1, l2);
-    //
-    //         }
-
-    //         ).await?;
-    //     }));
-    // }
-    // for task in tasks {
-    //     task.join();
-    // }
+        .buffered(num_cpus::get());
+    while let Some((name, bytes)) = built.next().await {
+        writer.add(&name, &bytes).await?;
+    }
+    writer.finish().await?;
+    tokio::fs::rename(&partial, dir.join(BUNDLE_FILE)).await?;
+    write_path(
+        &dir.join("manifest.json"),
+        serde_json::to_string_pretty(&selection.manifest)?.as_bytes(),
+    ).await?;
+    write_path(&dir.join(WORD_SETS_FILE), serde_json::to_string_pretty(&sets)?.as_bytes()).await?;
+    println!("Built {:?}", dir.join(BUNDLE_FILE));
     Ok(())
 }
 
-
 #[test]
 fn test_flat_trie() {
     let mut entries = vec!["ab", "abc", "abd"];
@@ -374,3 +619,33 @@ fn test_flat_trie() {
     println!("{:?}", b);
 }
 
+#[test]
+fn test_trie_bytes() {
+    let trie: Box<FlatTrie<LetterSet>> = ["tea", "toe", "tan"]
+        .iter()
+        .map(|x| (LetterSet::from_str(x), LetterSet::from_str(x)))
+        .collect();
+    let good = trie.to_bytes();
+    // Copied into words so the entries are aligned.
+    let mut words = vec![0u64; good.len().div_ceil(8)];
+    let aligned = unsafe { slice::from_raw_parts_mut(words.as_mut_ptr() as *mut u8, good.len()) };
+    aligned.copy_from_slice(&good);
+    let restored = FlatTrie::<LetterSet>::from_bytes(aligned).unwrap();
+    let mut found = vec![];
+    restored.search_all_subsets(LetterSet::from_str("toea"), &mut found);
+    assert_eq!(found, vec![LetterSet::from_str("tea"), LetterSet::from_str("toe")]);
+
+    let error = |bytes: &[u8]| check_trie_bytes(bytes).expect_err("corrupt trie was accepted");
+    let mut bytes = good.clone();
+    bytes.pop();
+    assert!(error(&bytes).contains("truncated"));
+    let mut bytes = good.clone();
+    *bytes.last_mut().unwrap() ^= 1;
+    assert!(error(&bytes).contains("hash"));
+    assert!(check_trie_header(&bytes).is_ok());
+    let mut bytes = good.clone();
+    bytes[12] += 1;
+    assert!(error(&bytes).contains("format version"));
+    assert!(error(&good[TRIE_HEADER_LEN..]).contains("not a trie file"));
+    assert!(error(&[]).contains("too short"));
+}
@@ -0,0 +1,258 @@
+//! A trie table packed into one file, so it loads with a single mmap and can be copied or
+//! embedded as a unit.
+//!
+//! The file starts with a 64-byte header (magic, endianness mark, version and section count),
+//! followed by a directory of 32-byte entries, each a zero-padded section name and the
+//! section's offset and length. Each section is a trie as written by [FlatTrie::to_bytes],
+//! starting on a 64-byte boundary.
+
+use std::collections::HashMap;
+use std::io;
+use std::io::SeekFrom;
+use std::ops::Range;
+use std::path::Path;
+
+use tokio::io::{AsyncSeek, AsyncSeekExt, AsyncWrite, AsyncWriteExt};
+
+use crate::trie::{check_trie_bytes, FlatTrie};
+use crate::util::persist::{mmap_bytes, MmapAllocator};
+
+const BUNDLE_MAGIC: [u8; 8] = *b"ACROBNDL";
+const BUNDLE_VERSION: u32 = 1;
+const ENDIAN_MARK: u32 = 0x0102_0304;
+const ALIGN: usize = 64;
+const DIRECTORY_ENTRY_LEN: usize = 32;
+const MAX_NAME_LEN: usize = 16;
+
+/// The name of a trie table's bundle file within its directory.
+pub const BUNDLE_FILE: &str = "tries.bundle";
+
+fn align(x: usize) -> usize {
+    x.div_ceil(ALIGN) * ALIGN
+}
+
+#[repr(C, align(64))]
+#[derive(Clone, Copy)]
+struct Block([u8; ALIGN]);
+
+enum Bytes {
+    Mapped(Box<[u8], MmapAllocator>),
+    Owned(Box<[Block]>, usize),
+}
+
+impl Bytes {
+    fn as_slice(&self) -> &[u8] {
+        match self {
+            Bytes::Mapped(x) => x,
+            Bytes::Owned(blocks, len) => unsafe {
+                std::slice::from_raw_parts(blocks.as_ptr() as *const u8, *len)
+            },
+        }
+    }
+}
+
+pub struct TrieBundle {
+    bytes: Bytes,
+    sections: HashMap<String, Range<usize>>,
+}
+
+impl TrieBundle {
+    /// Maps a bundle file, checking its header and directory. A section's header and length
+    /// are checked when [Self::get] hands it out; hashes by [Self::verify].
+    pub async fn open(path: &Path) -> io::Result<Self> {
+        let bytes = mmap_bytes(path).await?;
+        Self::parse(Bytes::Mapped(bytes)).map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{:?}: {}; rebuild it with `global trie`", path, e),
+            )
+        })
+    }
+    /// Reads a bundle from memory, such as one embedded in a test.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, String> {
+        let mut blocks = vec![Block([0; ALIGN]); bytes.len().div_ceil(ALIGN)].into_boxed_slice();
+        for (block, chunk) in blocks.iter_mut().zip(bytes.chunks(ALIGN)) {
+            block.0[..chunk.len()].copy_from_slice(chunk);
+        }
+        Self::parse(Bytes::Owned(blocks, bytes.len()))
+    }
+    fn parse(bytes: Bytes) -> Result<Self, String> {
+        let data = bytes.as_slice();
+        let u32_at = |i: usize| u32::from_ne_bytes(data[i..i + 4].try_into().unwrap());
+        let u64_at = |i: usize| u64::from_ne_bytes(data[i..i + 8].try_into().unwrap()) as usize;
+        if data.len() < ALIGN || data[0..8] != BUNDLE_MAGIC {
+            return Err("not a trie bundle".to_string());
+        }
+        if u32_at(8) != ENDIAN_MARK {
+            return Err("written on a machine of the other endianness".to_string());
+        }
+        if u32_at(12) != BUNDLE_VERSION {
+            return Err(format!(
+                "bundle version {}, but this build reads version {}",
+                u32_at(12),
+                BUNDLE_VERSION
+            ));
+        }
+        let count = u32_at(16) as usize;
+        if data.len() < ALIGN + count * DIRECTORY_ENTRY_LEN {
+            return Err("the directory is truncated".to_string());
+        }
+        let mut sections = HashMap::new();
+        for i in 0..count {
+            let entry = ALIGN + i * DIRECTORY_ENTRY_LEN;
+            let name = &data[entry..entry + MAX_NAME_LEN];
+            let name = String::from_utf8_lossy(name)
+                .trim_end_matches('\0')
+                .to_string();
+            let (offset, len) = (u64_at(entry + 16), u64_at(entry + 24));
+            if offset % ALIGN != 0 || offset.checked_add(len).is_none_or(|end| end > data.len()) {
+                return Err(format!(
+                    "section {} lies outside the file; it may be truncated",
+                    name
+                ));
+            }
+            sections.insert(name, offset..offset + len);
+        }
+        Ok(TrieBundle { bytes, sections })
+    }
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.sections.keys().map(|x| x.as_str())
+    }
+    /// A section's bytes, after checking its header and hash.
+    pub fn section(&self, name: &str) -> Result<&[u8], String> {
+        let range = self
+            .sections
+            .get(name)
+            .ok_or_else(|| format!("no section {}", name))?;
+        let bytes = &self.bytes.as_slice()[range.clone()];
+        check_trie_bytes(bytes).map_err(|e| format!("section {}: {}", name, e))?;
+        Ok(bytes)
+    }
+    /// Checks every section's hash, reading the whole bundle on a thread per CPU.
+    pub fn verify(&self) -> Result<(), String> {
+        let names: Vec<&str> = self.names().collect();
+        let chunk = names.len().div_ceil(num_cpus::get()).max(1);
+        std::thread::scope(|scope| {
+            let check = |names: &[&str]| -> Result<(), String> {
+                names.iter().try_for_each(|x| self.section(x).map(|_| ()))
+            };
+            let checks: Vec<_> = names
+                .chunks(chunk)
+                .map(|names| scope.spawn(move || check(names)))
+                .collect();
+            checks.into_iter().try_for_each(|x| x.join().unwrap())
+        })
+    }
+    /// The trie in a section, checked by [FlatTrie::from_bytes].
+    pub fn get<V: Clone>(&self, name: &str) -> Result<&FlatTrie<V>, String> {
+        let range = self
+            .sections
+            .get(name)
+            .ok_or_else(|| format!("no section {}", name))?;
+        FlatTrie::from_bytes(&self.bytes.as_slice()[range.clone()])
+            .map_err(|e| format!("section {}: {}", name, e))
+    }
+}
+
+/// Writes a bundle one section at a time, then fills in the header and directory.
+pub struct TrieBundleWriter<W> {
+    out: W,
+    capacity: usize,
+    directory: Vec<(String, usize, usize)>,
+    offset: usize,
+}
+
+impl<W: AsyncWrite + AsyncSeek + Unpin> TrieBundleWriter<W> {
+    /// Leaves room for a directory of up to `capacity` sections.
+    pub async fn new(mut out: W, capacity: usize) -> io::Result<Self> {
+        let offset = align(ALIGN + capacity * DIRECTORY_ENTRY_LEN);
+        out.write_all(&vec![0; offset]).await?;
+        Ok(TrieBundleWriter {
+            out,
+            capacity,
+            directory: vec![],
+            offset,
+        })
+    }
+    /// Appends a trie's bytes from [FlatTrie::to_bytes].
+    pub async fn add(&mut self, name: &str, bytes: &[u8]) -> io::Result<()> {
+        assert!(
+            name.len() <= MAX_NAME_LEN,
+            "section name {} is too long",
+            name
+        );
+        assert!(self.directory.len() < self.capacity, "too many sections");
+        self.out.write_all(bytes).await?;
+        let padding = align(bytes.len()) - bytes.len();
+        self.out.write_all(&vec![0; padding]).await?;
+        self.directory
+            .push((name.to_string(), self.offset, bytes.len()));
+        self.offset += bytes.len() + padding;
+        Ok(())
+    }
+    pub async fn finish(mut self) -> io::Result<W> {
+        let mut header = vec![0; align(ALIGN + self.capacity * DIRECTORY_ENTRY_LEN)];
+        header[0..8].copy_from_slice(&BUNDLE_MAGIC);
+        header[8..12].copy_from_slice(&ENDIAN_MARK.to_ne_bytes());
+        header[12..16].copy_from_slice(&BUNDLE_VERSION.to_ne_bytes());
+        header[16..20].copy_from_slice(&(self.directory.len() as u32).to_ne_bytes());
+        for (i, (name, offset, len)) in self.directory.iter().enumerate() {
+            let entry = &mut header[ALIGN + i * DIRECTORY_ENTRY_LEN..][..DIRECTORY_ENTRY_LEN];
+            entry[..name.len()].copy_from_slice(name.as_bytes());
+            entry[16..24].copy_from_slice(&(*offset as u64).to_ne_bytes());
+            entry[24..32].copy_from_slice(&(*len as u64).to_ne_bytes());
+        }
+        self.out.seek(SeekFrom::Start(0)).await?;
+        self.out.write_all(&header).await?;
+        self.out.flush().await?;
+        Ok(self.out)
+    }
+}
+
+#[tokio::test]
+async fn test_trie_bundle() -> anyhow::Result<()> {
+    use acrostic_core::letter::LetterSet;
+    let trie = |words: &[&str]| -> Box<FlatTrie<LetterSet>> {
+        words
+            .iter()
+            .map(|x| (LetterSet::from_str(x), LetterSet::from_str(x)))
+            .collect()
+    };
+    let mut writer = TrieBundleWriter::new(io::Cursor::new(vec![]), 3).await?;
+    writer
+        .add("unary/T", &trie(&["tea", "toe"]).to_bytes())
+        .await?;
+    writer.add("unary/A", &trie(&["ant"]).to_bytes()).await?;
+    let bytes = writer.finish().await?.into_inner();
+
+    let bundle = TrieBundle::from_bytes(&bytes).map_err(anyhow::Error::msg)?;
+    let mut names: Vec<&str> = bundle.names().collect();
+    names.sort();
+    assert_eq!(names, vec!["unary/A", "unary/T"]);
+    let mut found = vec![];
+    let t = bundle
+        .get::<LetterSet>("unary/T")
+        .map_err(anyhow::Error::msg)?;
+    t.search_all_subsets(LetterSet::from_str("toea"), &mut found);
+    assert_eq!(found.len(), 2);
+    assert!(bundle.get::<LetterSet>("unary/B").is_err());
+    assert!(bundle
+        .get::<(LetterSet, LetterSet)>("unary/A")
+        .unwrap_err()
+        .contains("bytes"));
+
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().join(BUNDLE_FILE);
+    tokio::fs::write(&path, &bytes).await?;
+    assert!(TrieBundle::open(&path).await?.verify().is_ok());
+    tokio::fs::write(&path, &bytes[..bytes.len() - 64]).await?;
+    let error = TrieBundle::open(&path).await.err().unwrap().to_string();
+    assert!(error.contains("truncated"), "{}", error);
+    let mut corrupt = bytes.clone();
+    corrupt[bundle.sections["unary/A"].end - 1] ^= 1;
+    let bundle = TrieBundle::from_bytes(&corrupt).map_err(anyhow::Error::msg)?;
+    assert!(bundle.get::<LetterSet>("unary/A").is_ok());
+    assert!(bundle.verify().unwrap_err().contains("hash"));
+    assert!(TrieBundle::open(&dir.path().join("missing")).await.is_err());
+    Ok(())
+}
@@ -4,16 +4,17 @@ use std::io;
 use std::sync::LazyLock;
 
 use acrostic_core::letter::{Letter, LetterMap, LetterSet};
-use itertools::Itertools;
+use anyhow::anyhow;
 use safe_once_async::async_lazy::AsyncLazy;
 use safe_once_async::detached::{JoinTransparent, spawn_transparent};
 use safe_once_async::sync::AsyncLazyLock;
 use crate::dict::{FlatWord, FLAT_WORDS};
-use crate::trie::{FlatTrie, FlatTrieEntry};
-use crate::util::alloc::MmapAllocator;
+use crate::dict_manifest::{DictSelection, DICT_SELECTION};
+use crate::trie::{sections, selected_word_sets, FlatTrie, FlatTrieEntry, WordSets, WORD_SETS_FILE};
+use crate::trie_bundle::{TrieBundle, BUNDLE_FILE};
 use crate::util::lazy_async::CloneError;
 use crate::util::persist::PersistentFile;
-use crate::PACKAGE_PATH;
+use crate::{read_path_to_string, PACKAGE_PATH};
 // use crate::util::lazy_async::LazyAsync;
 
 // use crate::util::lazy_async::LazyAsync;
@@ -21,8 +22,10 @@ use crate::PACKAGE_PATH;
 
 pub struct FlatTrieTable {
     pub dict: &'static [FlatWord],
-    pub unary: LetterMap<Box<FlatTrie<LetterSet>, MmapAllocator>>,
-    pub binary: HashMap<(Letter, Letter), Box<FlatTrie<(LetterSet, LetterSet)>, MmapAllocator>>,
+    /// The dictionary manifest the tries were built from.
+    pub selection: &'static DictSelection,
+    pub unary: LetterMap<&'static FlatTrie<LetterSet>>,
+    pub binary: HashMap<(Letter, Letter), &'static FlatTrie<(LetterSet, LetterSet)>>,
 }
 
 pub static FLAT_TRIE_TABLE: LazyLock<AsyncLazyLock<JoinTransparent<anyhow::Result<FlatTrieTable>>>> =
@@ -32,35 +35,83 @@ pub static FLAT_TRIE_TABLE: LazyLock<AsyncLazyLock<JoinTransparent<anyhow::Resul
 
 impl FlatTrieTable {
     async fn new() -> anyhow::Result<Self> {
-        unsafe {
-            let mut unary: LetterMap<Option<Box<FlatTrie<LetterSet>, MmapAllocator>>> =
-                LetterMap::new();
-            for x in Letter::all() {
-                unary[x] = Some(
-                    FlatTrie::restore(&PACKAGE_PATH.join(&format!("build/unary/map_{}.dat", x)))
-                        .await?,
-                );
+        let selection: &'static DictSelection = DICT_SELECTION.get().await.clone_error_static()?;
+        let dir = selection.dir();
+        let stored = match read_path_to_string(&dir.join(WORD_SETS_FILE)).await {
+            Ok(stored) => stored,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                return Err(anyhow!(
+                    "no complete trie table for dictionary manifest {} in {:?}; run `global trie` \
+                     with the same --dict-manifest",
+                    selection.key,
+                    dir
+                ));
             }
-            let unary = unary.map(|x| x.unwrap());
-            let mut binary = HashMap::new();
-            for ls in Letter::all().combinations_with_replacement(2) {
-                let l1 = ls[0];
-                let l2 = ls[1];
-                binary.insert(
-                    (l1, l2),
-                    FlatTrie::<(LetterSet, LetterSet)>::restore(
-                        &PACKAGE_PATH.join(&format!("build/binary/map_{}_{}.dat", l1, l2)),
-                    )
-                    .await?,
-                );
+            Err(e) => return Err(e.into()),
+        };
+        let dict = FLAT_WORDS.get_static().await?;
+        check_word_sets(&stored, selection, dict).map_err(|e| anyhow!("{:?}: {}", dir, e))?;
+        // The table lives as long as the program, so the bundle it borrows from does too.
+        let path = dir.join(BUNDLE_FILE);
+        let bundle: &'static TrieBundle = Box::leak(Box::new(TrieBundle::open(&path).await?));
+        // Every entry is trusted once loaded, so each section's hash is checked up front.
+        tokio::task::spawn_blocking(move || bundle.verify())
+            .await?
+            .map_err(|e| anyhow!("{:?}: {}; rebuild it with `global trie`", path, e))?;
+        Self::from_bundle(dict, selection, bundle)
+    }
+    /// A table over the tries in `bundle`, which must hold every section of [sections].
+    pub fn from_bundle(
+        dict: &'static [FlatWord],
+        selection: &'static DictSelection,
+        bundle: &'static TrieBundle,
+    ) -> anyhow::Result<Self> {
+        let mut unary: LetterMap<Option<&'static FlatTrie<LetterSet>>> = LetterMap::new();
+        let mut binary = HashMap::new();
+        for (name, letters) in sections() {
+            let error = |e: String| anyhow!("{}; rebuild it with `global trie`", e);
+            match letters[..] {
+                [l] => unary[l] = Some(bundle.get(&name).map_err(error)?),
+                [l1, l2] => {
+                    binary.insert((l1, l2), bundle.get(&name).map_err(error)?);
+                }
+                _ => unreachable!(),
             }
-            Ok(FlatTrieTable {
-                dict: FLAT_WORDS.get_static().await?,
-                unary,
-                binary,
-            })
         }
+        Ok(FlatTrieTable {
+            dict,
+            selection,
+            unary: unary.map(|x| x.unwrap()),
+            binary,
+        })
+    }
+    /// The dictionary words in the tries, most frequent first.
+    pub fn words(&self) -> Vec<&'static FlatWord> {
+        self.selection.words(self.dict)
+    }
+}
+
+/// Fails unless `stored`, a table's [WORD_SETS_FILE], matches the words `selection` takes
+/// from `dict` now. The table key only covers the manifest, so this is what notices a
+/// dictionary rebuilt since the table was.
+fn check_word_sets(
+    stored: &str,
+    selection: &DictSelection,
+    dict: &[FlatWord],
+) -> anyhow::Result<()> {
+    let stored: WordSets = serde_json::from_str(stored)?;
+    if stored != selected_word_sets(selection, dict) {
+        return Err(anyhow!(
+            "the trie table was built from a different dictionary; rebuild it with `global trie`"
+        ));
     }
+    Ok(())
+}
+
+/// Loads the trie table for `--dict-manifest`, which checks the hash of every trie in it.
+pub async fn verify_trie_table() -> anyhow::Result<()> {
+    FLAT_TRIE_TABLE.get().await.clone_error_static()?;
+    Ok(())
 }
 
 // #[test]
@@ -75,3 +126,66 @@ impl FlatTrieTable {
 //         format!("{:?}", x.1);
 //     }
 // }
+
+/// A table over `words`, built in memory, for tests that should not depend on `build/`.
+#[cfg(test)]
+pub async fn fixture(words: &[&str]) -> anyhow::Result<FlatTrieTable> {
+    use crate::dict_manifest::DictManifest;
+    fixture_with(words, DictManifest { min_letters: 0, ..DictManifest::default() }).await
+}
+
+/// Like [fixture], with only the words `manifest` selects in the tries.
+#[cfg(test)]
+pub async fn fixture_with(
+    words: &[&str],
+    manifest: crate::dict_manifest::DictManifest,
+) -> anyhow::Result<FlatTrieTable> {
+    use crate::dict::test_dict;
+    use crate::trie::section_bytes;
+    use crate::trie_bundle::TrieBundleWriter;
+    let dict = test_dict(&words.iter().map(|x| (*x, 1)).collect::<Vec<_>>());
+    let selection = Box::leak(Box::new(DictSelection::new(manifest).await?));
+    let mut by_letter = LetterMap::<Vec<LetterSet>>::new();
+    for word in selection.words(dict) {
+        by_letter[word.letter_vec[0]].push(word.letters);
+    }
+    let sections = sections();
+    let mut writer = TrieBundleWriter::new(io::Cursor::new(vec![]), sections.len()).await?;
+    for (name, letters) in sections {
+        let words2 = letters.get(1).map(|l| &by_letter[*l][..]);
+        writer.add(&name, &section_bytes(&by_letter[letters[0]], words2)).await?;
+    }
+    let bytes = writer.finish().await?.into_inner();
+    let bundle = TrieBundle::from_bytes(&bytes).map_err(|e| anyhow!(e))?;
+    FlatTrieTable::from_bundle(dict, selection, Box::leak(Box::new(bundle)))
+}
+
+#[tokio::test]
+async fn test_fixture() -> anyhow::Result<()> {
+    let table = fixture(&["tea", "toe", "ant", "not"]).await?;
+    assert_eq!(table.words().len(), 4);
+    let t = Letter::new(b't').unwrap();
+    let n = Letter::new(b'n').unwrap();
+    let mut found = vec![];
+    table.unary[t].search_all_subsets(LetterSet::from_str("teax"), &mut found);
+    assert_eq!(found, vec![LetterSet::from_str("tea")]);
+    let mut found = vec![];
+    table.binary[&(n, t)].search_all_subsets(LetterSet::from_str("nottea"), &mut found);
+    assert_eq!(found, vec![(LetterSet::from_str("not"), LetterSet::from_str("tea"))]);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_check_word_sets() -> anyhow::Result<()> {
+    use crate::dict::test_dict;
+    use crate::dict_manifest::DictManifest;
+    let manifest = DictManifest { min_letters: 0, ..DictManifest::default() };
+    let selection = DictSelection::new(manifest).await?;
+    let old = test_dict(&[("tea", 1), ("toe", 1)]);
+    let stored = serde_json::to_string(&selected_word_sets(&selection, old))?;
+    check_word_sets(&stored, &selection, old)?;
+    let new = test_dict(&[("tea", 1), ("toe", 1), ("tie dye", 1)]);
+    let error = check_word_sets(&stored, &selection, new).unwrap_err();
+    assert!(error.to_string().contains("global trie"), "{}", error);
+    Ok(())
+}
@@ -55,22 +55,30 @@ pub async fn save_vec<T>(file: &Path, value: &[T]) -> io::Result<()> {
 
 
 pub async fn restore_vec<T>(filename: &Path) -> io::Result<Box<[T], MmapAllocator>> {
+    let cannot =
+        |e: io::Error| io::Error::new(e.kind(), format!("Cannot map {:?}: {}", filename, e));
+    let file = tokio::fs::OpenOptions::new()
+        .read(true)
+        .open(filename)
+        .await
+        .map_err(cannot)?;
+    let len = file.metadata().await?.len() as usize;
+    if len % size_of::<T>() != 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("{:?} is {} bytes, which is not a whole number of entries", filename, len),
+        ));
+    }
     Ok(unsafe {
-        let file = tokio::fs::OpenOptions::new()
-            .read(true)
-            .open(filename).await
-            .unwrap_or_else(|e| panic!("Cannot open {:?}: {}", filename, e));
-        let len = file.metadata().await?.len();
         if len == 0 {
             Box::from_raw_in(&mut [], MmapAllocator { mmap: None, file })
         } else {
             let file = file.into_std().await;
-            let mmap = MmapOptions::new().map_copy(&file).unwrap();
+            let mmap = MmapOptions::new().map_copy(&file).map_err(cannot)?;
             let file = tokio::fs::File::from_std(file);
-            assert_eq!(mmap.len() as u64, len);
-            assert_eq!(mmap.len() % size_of::<T>(), 0);
+            assert_eq!(mmap.len(), len);
             Box::from_raw_in(
-                slice::from_raw_parts_mut(mmap.as_ptr() as *mut T, mmap.len() / size_of::<T>()),
+                slice::from_raw_parts_mut(mmap.as_ptr() as *mut T, len / size_of::<T>()),
                 MmapAllocator {
                     mmap: Some(mmap),
                     file,
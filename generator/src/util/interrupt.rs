@@ -2,11 +2,21 @@ use futures::future::BoxFuture;
 use safe_once_async::detached::{spawn_transparent, JoinTransparent};
 use std::future::Future;
 use std::mem;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering::Relaxed;
 use tokio::select;
 use tokio::signal::ctrl_c;
 use tokio::sync::mpsc;
 use tokio::task::JoinHandle;
 
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// Whether Ctrl-C has been pressed. Dropping the main future cannot stop `spawn_blocking`
+/// work, so long-running blocking loops poll this and return early instead.
+pub fn is_interrupted() -> bool {
+    INTERRUPTED.load(Relaxed)
+}
+
 type Cleanup = Box<dyn 'static + Send + FnOnce() -> JoinTransparent<anyhow::Result<()>>>;
 
 #[derive(Clone)]
@@ -42,7 +52,10 @@ pub async fn run_with_interrupts<
 ) -> anyhow::Result<()> {
     let (tx, rx) = channel();
     let () = select!(
-        result = ctrl_c() => anyhow::Result::<()>::Ok(result?),
+        result = ctrl_c() => {
+            INTERRUPTED.store(true, Relaxed);
+            anyhow::Result::<()>::Ok(result?)
+        },
         result = spawn_transparent(f(tx)) => anyhow::Result::<()>::Ok(result?),
     )?;
     let () = select!(
@@ -29,18 +29,20 @@ unsafe impl Allocator for MmapAllocator {
 }
 
 pub async fn mmap_bytes(filename: &Path) -> io::Result<Box<[u8], MmapAllocator>> {
+    let cannot =
+        |e: io::Error| io::Error::new(e.kind(), format!("Cannot map {:?}: {}", filename, e));
+    let file = tokio::fs::OpenOptions::new()
+        .read(true)
+        .open(filename)
+        .await
+        .map_err(cannot)?;
+    let len = file.metadata().await?.len();
     Ok(unsafe {
-        let file = tokio::fs::OpenOptions::new()
-            .read(true)
-            .open(filename)
-            .await
-            .unwrap_or_else(|e| panic!("Cannot open {:?}: {}", filename, e));
-        let len = file.metadata().await?.len();
         if len == 0 {
             Box::from_raw_in(&mut [], MmapAllocator { mmap: None, file })
         } else {
             let file = file.into_std().await;
-            let mmap = MmapOptions::new().map_copy(&file).unwrap();
+            let mmap = MmapOptions::new().map_copy(&file).map_err(cannot)?;
             let file = tokio::fs::File::from_std(file);
             assert_eq!(mmap.len() as u64, len);
             Box::from_raw_in(
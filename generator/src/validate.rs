@@ -0,0 +1,206 @@
+use std::collections::HashMap;
+use std::io;
+use std::panic::catch_unwind;
+
+use acrostic_core::puzzle::{Puzzle, ValidationError};
+use anyhow::anyhow;
+use serde::Serialize;
+
+use crate::add_letters::quote_to_cells;
+use crate::cli::ValidateArgs;
+use crate::clues::{contains_subsequence, ClueScorer};
+use crate::pipeline::Stage;
+use crate::read_path_to_string;
+use crate::string::LetterString;
+use crate::trie_table::verify_trie_table;
+use crate::PUZZLES_PATH;
+
+/// Something wrong with one stage file.
+#[derive(Serialize, Debug)]
+#[serde(untagged)]
+pub enum Problem {
+    Structure(ValidationError),
+    Content(ContentProblem),
+}
+
+/// Problems that [Puzzle::validate] cannot see because they depend on the generator's
+/// text processing or dictionaries.
+#[derive(Serialize, Debug)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ContentProblem {
+    Unreadable { error: String },
+    QuoteLetters { expected: String, found: String },
+    SourceLetters { expected: String, found: String },
+    RepeatedAnswer { answer: String, clues: [usize; 2] },
+    ClueContainsAnswer { clue: usize, form: String },
+}
+
+#[derive(Serialize)]
+struct ProblemLine<'a> {
+    puzzle: usize,
+    stage: String,
+    #[serde(flatten)]
+    problem: &'a Problem,
+}
+
+fn check_letters(puzzle: &Puzzle, problems: &mut Vec<Problem>) {
+    if let Some(found) = &puzzle.quote_letters {
+        match catch_unwind(|| quote_to_cells(&puzzle.quote)) {
+            Ok(expected) if expected == *found => {}
+            Ok(expected) => problems.push(Problem::Content(ContentProblem::QuoteLetters {
+                expected,
+                found: found.clone(),
+            })),
+            Err(_) => problems.push(Problem::Content(ContentProblem::Unreadable {
+                error: "quote contains a character quote_to_cells does not handle".to_string(),
+            })),
+        }
+    }
+    if let Some(found) = &puzzle.source_letters {
+        let expected: String = LetterString::from_str(&puzzle.source)
+            .iter()
+            .map(|x| x.to_char())
+            .collect();
+        if expected != *found {
+            problems.push(Problem::Content(ContentProblem::SourceLetters {
+                expected,
+                found: found.clone(),
+            }));
+        }
+    }
+}
+
+fn check_answers(puzzle: &Puzzle, scorer: &ClueScorer, problems: &mut Vec<Problem>) {
+    let Some(clues) = &puzzle.clues else {
+        return;
+    };
+    let mut seen = HashMap::new();
+    for (i, clue) in clues.iter().enumerate() {
+        if let Some(first) = seen.insert(clue.answer_letters.as_str(), i) {
+            problems.push(Problem::Content(ContentProblem::RepeatedAnswer {
+                answer: clue.answer.clone(),
+                clues: [first, i],
+            }));
+        }
+        let Some(text) = &clue.clue else {
+            continue;
+        };
+        let answer = LetterString::from_str(&clue.answer);
+        let form = if contains_subsequence(&LetterString::from_str(text), &answer) {
+            Some(clue.answer.clone())
+        } else {
+            scorer.conflict(&clue.answer, text)
+        };
+        if let Some(form) = form {
+            problems.push(Problem::Content(ContentProblem::ClueContainsAnswer { clue: i, form }));
+        }
+    }
+}
+
+/// Checks one stage file, returning `None` if it does not exist.
+pub async fn validate_file(
+    pindex: usize,
+    stage: Stage,
+    scorer: &ClueScorer,
+) -> io::Result<Option<Vec<Problem>>> {
+    let input = match read_path_to_string(&stage.output_path(pindex)).await {
+        Ok(x) => x,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e),
+    };
+    let mut problems = vec![];
+    let puzzle = match Puzzle::from_json(&input) {
+        Ok(x) => x,
+        Err(e) => {
+            problems.push(Problem::Content(ContentProblem::Unreadable {
+                error: e.to_string(),
+            }));
+            return Ok(Some(problems));
+        }
+    };
+    if let Err(e) = puzzle.validate() {
+        problems.push(Problem::Structure(e));
+    }
+    check_letters(&puzzle, &mut problems);
+    check_answers(&puzzle, scorer, &mut problems);
+    Ok(Some(problems))
+}
+
+async fn all_puzzle_indices() -> io::Result<Vec<usize>> {
+    let mut puzzles = vec![];
+    let mut dir = tokio::fs::read_dir(&*PUZZLES_PATH).await?;
+    while let Some(entry) = dir.next_entry().await? {
+        if let Some(pindex) = entry.file_name().to_str().and_then(|x| x.parse().ok()) {
+            puzzles.push(pindex);
+        }
+    }
+    puzzles.sort();
+    Ok(puzzles)
+}
+
+/// Prints one JSON line per problem to stdout and a summary to stderr.
+pub async fn validate_puzzles(args: &ValidateArgs) -> anyhow::Result<()> {
+    if args.tries {
+        verify_trie_table().await?;
+        eprintln!("trie table hashes match");
+    }
+    let puzzles = if args.puzzles.is_empty() {
+        all_puzzle_indices().await?
+    } else {
+        args.puzzle_indices()
+    };
+    let scorer = ClueScorer::new().await?;
+    let mut files = 0;
+    let mut problems = 0;
+    let mut broken = 0;
+    for pindex in puzzles {
+        let mut puzzle_broken = false;
+        for stage in Stage::ALL {
+            let Some(found) = validate_file(pindex, stage, &scorer).await? else {
+                continue;
+            };
+            files += 1;
+            for problem in &found {
+                let line = ProblemLine {
+                    puzzle: pindex,
+                    stage: stage.to_string(),
+                    problem,
+                };
+                println!("{}", serde_json::to_string(&line)?);
+            }
+            problems += found.len();
+            puzzle_broken |= !found.is_empty();
+        }
+        broken += puzzle_broken as usize;
+    }
+    eprintln!("{} files, {} problems in {} puzzles", files, problems, broken);
+    if problems > 0 {
+        return Err(anyhow!("{} puzzles have problems", broken));
+    }
+    Ok(())
+}
+
+#[test]
+fn test_problem_json() {
+    let line = ProblemLine {
+        puzzle: 3,
+        stage: Stage::Answers.to_string(),
+        problem: &Problem::Structure(ValidationError::DuplicateIndex { index: 7 }),
+    };
+    assert_eq!(
+        serde_json::to_string(&line).unwrap(),
+        r#"{"puzzle":3,"stage":"answers","kind":"duplicate_index","index":7}"#
+    );
+    let line = ProblemLine {
+        puzzle: 3,
+        stage: Stage::Chat.to_string(),
+        problem: &Problem::Content(ContentProblem::RepeatedAnswer {
+            answer: "tat".to_string(),
+            clues: [0, 4],
+        }),
+    };
+    assert_eq!(
+        serde_json::to_string(&line).unwrap(),
+        r#"{"puzzle":3,"stage":"chat","kind":"repeated_answer","answer":"tat","clues":[0,4]}"#
+    );
+}
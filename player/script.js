@@ -1,4 +1,6 @@
 CELL_SIZE = 10
+// Newest puzzle file format this player understands; see acrostic_core::puzzle::FORMAT_VERSION.
+FORMAT_VERSION = 1
 
 class CellValue {
     constructor(id, correct) {
@@ -24,6 +26,10 @@ class Cell {
     render(selected_grid, selected_value) {
         this.nodeCell.className = "entry-cell "
 
+        if (this.wordStart) {
+            this.nodeCell.className += "word-start "
+        }
+
         if (this.value.guess == " ") {
             this.nodeText.nodeValue = ""
             this.nodeCell.className += "content-space "
@@ -226,6 +232,7 @@ class Puzzle {
             p.appendChild(document.createElement("br"))
             for (var i = 0; i < clue.answer_letters.length; i++) {
                 var cell = new Cell(this.quote.cells[clue.indices[i]].value)
+                cell.wordStart = clue.breaks != null && clue.breaks.includes(i)
                 grid.addCell(cell)
             }
             this.clues[index] = grid
@@ -384,15 +391,38 @@ class Puzzle {
 
 class Index {
     constructor(index) {
-        this.div = document.createElement("p")
-        for (const p of index.links) {
+        this.div = document.createElement("div")
+        const packs = index.packs || []
+        for (const pack of packs) {
+            let h = document.createElement("h2")
+            h.appendChild(document.createTextNode(pack.title))
+            this.div.appendChild(h)
+            if (pack.description) {
+                let d = document.createElement("p")
+                d.appendChild(document.createTextNode(pack.description))
+                this.div.appendChild(d)
+            }
+            this.div.appendChild(Index.links(pack.links))
+        }
+        if (packs.length > 0) {
+            let h = document.createElement("h2")
+            h.appendChild(document.createTextNode("All puzzles"))
+            this.div.appendChild(h)
+        }
+        this.div.appendChild(Index.links(index.links))
+    }
+
+    static links(links) {
+        let p = document.createElement("p")
+        for (const link of links) {
             let a = document.createElement("a")
-            a.href = ".?puzzle=" + encodeURIComponent(p.url)
-            let t = document.createTextNode(p.name)
+            a.href = ".?puzzle=" + encodeURIComponent(link.url)
+            let t = document.createTextNode(link.name)
             a.appendChild(t)
-            this.div.appendChild(a)
-            this.div.appendChild(document.createElement("br"))
+            p.appendChild(a)
+            p.appendChild(document.createElement("br"))
         }
+        return p
     }
 }
 
@@ -403,6 +433,12 @@ async function load_puzzle(url, room) {
         socket = new WebSocket(room);
     }
     var puzzle = await data.json()
+    if ((puzzle.format_version || 0) > FORMAT_VERSION) {
+        let p = document.createElement("p")
+        p.appendChild(document.createTextNode("This puzzle needs a newer version of the player."))
+        document.getElementById("contents").appendChild(p)
+        return
+    }
     puzzle = new Puzzle(url, puzzle, socket)
     document.getElementById("contents").appendChild(puzzle.div)
     document.addEventListener('keydown', function (event) { puzzle.onKeydown(event) });